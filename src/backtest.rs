@@ -0,0 +1,426 @@
+use std::fmt;
+
+use anyhow::anyhow;
+
+use crate::{
+    config::AppConfig,
+    dataset::{Candle, DataSet},
+    market::Market,
+    model::Model,
+    position::{ExitReason, ExitRules},
+    simulated_market::SimulatedMarket,
+    utils::calculate_profit,
+};
+
+/// A single simulated round trip.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trade {
+    pub entry_time: u64,
+    pub entry_price: f64,
+    pub exit_time: u64,
+    pub exit_price: f64,
+    pub quantity: f64,
+    pub profit: f64,
+    pub profit_percentage: f64,
+    pub reason: ExitReason,
+}
+
+/// A position that was bought but hasn't been sold (yet).
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenTrade {
+    /// Index of the candle the trade was opened in.
+    pub entry_index: usize,
+    pub entry_time: u64,
+    pub entry_price: f64,
+    pub quantity: f64,
+    pub target: f64,
+    pub highest_price: f64,
+}
+
+/// Outcome of a backtest.
+#[derive(Debug, Clone)]
+pub struct BacktestReport {
+    pub trades: Vec<Trade>,
+    /// Position still open at the end of the data, if any.
+    pub open_trade: Option<OpenTrade>,
+    pub initial_equity: f64,
+    /// Quote balance plus the value of the open position at the last close.
+    pub final_equity: f64,
+}
+
+/// Replays historical candles through the same train, predict, buy and sell cycle as `LightGBMStrategy`.
+/// Orders are filled by a `SimulatedMarket`, so fees are taken into account.
+pub struct Backtest<'a> {
+    config: &'a AppConfig,
+    model: &'a Model,
+}
+
+impl<'a> Backtest<'a> {
+    pub fn new(config: &'a AppConfig, model: &'a Model) -> Self {
+        Self { config, model }
+    }
+
+    /// Runs the backtest over the given candles, oldest first.
+    pub fn run(&self, candles: &[Candle]) -> anyhow::Result<BacktestReport> {
+        let label = &self.config.dataset.label;
+        let retrain_interval = self.config.backtest.retrain_interval.max(1);
+
+        // Otherwise there's nothing to train on before the first prediction.
+        let features_warmup = self.config.dataset.features.warmup();
+        if self.config.backtest.warmup <= features_warmup + label.horizon() {
+            return Err(anyhow!(
+                "backtest.warmup must be longer than the feature warmup ({}) plus the label horizon ({})",
+                features_warmup,
+                label.horizon()
+            ));
+        }
+
+        // Features and labels only depend on past and future candles respectively,
+        // so they can be computed once and sliced at every step.
+        let features = self.config.dataset.features.compute(candles);
+        let labels = label.build(candles);
+
+        let mut booster: Option<lightgbm::Booster> = None;
+        let mut trained_at = 0;
+
+        self.simulate(candles, |i| {
+            // Only the candles before the current one are closed, which labels the ones before `i - horizon`.
+            let latest_features = match &features[i - 1] {
+                Some(features) => features.clone(),
+                None => return Ok(None),
+            };
+
+            if booster.is_none() || i - trained_at >= retrain_interval {
+                let end = i - label.horizon();
+                let dataset = DataSet::from_parts(&features[..end], &labels[..end]);
+                // None of the labeled candles has its features yet.
+                if !dataset.1.is_empty() {
+                    debug!("Training model on {} candles.", end);
+                    booster = Some(self.model.train(dataset)?);
+                    trained_at = i;
+                }
+            }
+
+            let prediction = match &booster {
+                Some(booster) => booster.predict(vec![latest_features])?,
+                None => return Ok(None),
+            };
+
+            Ok(Some(prediction[0][0]))
+        })
+    }
+
+    /// Walks over the candles after the warmup period.
+    /// `predict` is called with the index of the current candle whenever no position is open and must return the predicted price,
+    /// or `None` when no prediction can be made yet.
+    fn simulate<F>(&self, candles: &[Candle], mut predict: F) -> anyhow::Result<BacktestReport>
+    where
+        F: FnMut(usize) -> anyhow::Result<Option<f64>>,
+    {
+        let warmup = self.config.backtest.warmup.max(1);
+        if candles.len() <= warmup {
+            return Err(anyhow!(
+                "not enough candles to backtest: got {}, need more than {} for warmup",
+                candles.len(),
+                warmup
+            ));
+        }
+
+        let symbol = &self.config.symbol;
+        let amount = self.config.trade.amount;
+        // Backtests run offline, without the exchange info that names the assets, and only the balances matter.
+        let market = SimulatedMarket::new(
+            symbol,
+            "BASE",
+            "QUOTE",
+            self.config.backtest.balance,
+            self.config.backtest.fee,
+        );
+        let mut trades: Vec<Trade> = Vec::new();
+        let mut open_trade: Option<OpenTrade> = None;
+        let exit_rules = ExitRules::from_config(&self.config.trade);
+
+        for (i, candle) in candles.iter().enumerate().skip(warmup) {
+            // Buy at the open when the predicted high is above it, just like the live bot.
+            if open_trade.is_none() {
+                let score = match predict(i)? {
+                    Some(score) => score,
+                    None => continue,
+                };
+
+                if score < candle.open {
+                    debug!(
+                        "Predicted value {} is lower than the open ({}) price, skipping candle {}.",
+                        score, candle.open, candle.open_time
+                    );
+                    continue;
+                }
+
+                market.set_price(candle.open);
+                let fill = match market.place_buy_order(symbol, amount, false) {
+                    Ok(fill) => fill,
+                    Err(err) => {
+                        warn!("Skipping candle {}: {}.", candle.open_time, err);
+                        continue;
+                    }
+                };
+
+                open_trade = Some(OpenTrade {
+                    entry_index: i,
+                    entry_time: candle.open_time,
+                    entry_price: fill.price,
+                    quantity: fill.quantity,
+                    target: score,
+                    highest_price: fill.price,
+                });
+            }
+
+            // Sell as soon as an exit is hit, which may be the entry candle itself.
+            if let Some(trade) = open_trade.as_mut() {
+                let (reason, price) = match self.exit(&exit_rules, trade, candle, i) {
+                    Some(exit) => exit,
+                    None => {
+                        trade.highest_price = trade.highest_price.max(candle.high);
+                        continue;
+                    }
+                };
+
+                market.set_price(price);
+                let fill = market.place_sell_order(symbol, trade.quantity, false)?;
+
+                let proceeds = fill.quantity * fill.price - fill.commission;
+                let (profit, profit_percentage) = calculate_profit(amount, amount, proceeds);
+                trades.push(Trade {
+                    entry_time: trade.entry_time,
+                    entry_price: trade.entry_price,
+                    exit_time: candle.open_time,
+                    exit_price: fill.price,
+                    quantity: fill.quantity,
+                    profit,
+                    profit_percentage,
+                    reason,
+                });
+                open_trade = None;
+            }
+        }
+
+        let last_close = candles.last().unwrap().close;
+        let final_equity = market.quote_balance() + market.base_balance() * last_close;
+
+        Ok(BacktestReport {
+            trades,
+            open_trade,
+            initial_equity: self.config.backtest.balance,
+            final_equity,
+        })
+    }
+
+    /// Returns why and at which price the open trade is sold during the given candle, if at all.
+    /// Only OHLC data is known, so stops within a candle are assumed to be hit before the target.
+    fn exit(
+        &self,
+        exit_rules: &ExitRules,
+        trade: &OpenTrade,
+        candle: &Candle,
+        index: usize,
+    ) -> Option<(ExitReason, f64)> {
+        let candles_held = (index - trade.entry_index) as u64;
+
+        // The open may already be past an exit, e.g. after a gap or once the maximum holding time is reached.
+        let reason = exit_rules.check(
+            trade.entry_price,
+            trade.target,
+            trade.highest_price,
+            candle.open,
+            candles_held,
+        );
+        if let Some(reason) = reason {
+            return Some((reason, candle.open));
+        }
+
+        if let Some(stop_price) = exit_rules.stop_price(trade.entry_price, trade.highest_price) {
+            if candle.low <= stop_price {
+                let reason = exit_rules.check(
+                    trade.entry_price,
+                    trade.target,
+                    trade.highest_price,
+                    stop_price,
+                    candles_held,
+                )?;
+                return Some((reason, stop_price));
+            }
+        }
+
+        if candle.high >= trade.target {
+            return Some((ExitReason::Target, trade.target));
+        }
+
+        None
+    }
+}
+
+impl fmt::Display for BacktestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<15} {:>15} {:<15} {:>15} {:>15} {:>10}  reason",
+            "entry_time", "entry_price", "exit_time", "exit_price", "profit", "profit_%"
+        )?;
+        for trade in &self.trades {
+            writeln!(
+                f,
+                "{:<15} {:>15.4} {:<15} {:>15.4} {:>15.4} {:>10.4}  {}",
+                trade.entry_time,
+                trade.entry_price,
+                trade.exit_time,
+                trade.exit_price,
+                trade.profit,
+                trade.profit_percentage,
+                trade.reason
+            )?;
+        }
+        if let Some(trade) = &self.open_trade {
+            writeln!(
+                f,
+                "{:<15} {:>15.4} {:<15} {:>15.4} (open)",
+                trade.entry_time, trade.entry_price, "-", trade.target
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Trades: {}", self.trades.len())?;
+        writeln!(f, "Initial equity: {:.4}", self.initial_equity)?;
+        write!(f, "Final equity: {:.4}", self.final_equity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{config::try_load_config, dataset::Candle, model::Model};
+
+    use super::Backtest;
+    use crate::position::ExitReason;
+
+    fn candle(open_time: u64, open: f64, high: f64, close: f64) -> Candle {
+        Candle {
+            open_time,
+            close_time: open_time + 3599999,
+            open,
+            high,
+            low: open.min(close),
+            close,
+            volume: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_simulate() {
+        let mut config = try_load_config("config.example.yaml");
+        config.trade.amount = 100.0;
+        config.backtest.balance = 1000.0;
+        config.backtest.fee = 0.0;
+        config.backtest.warmup = 1;
+        let model = Model::new();
+        let backtest = Backtest::new(&config, &model);
+
+        let candles = vec![
+            candle(0, 10.0, 11.0, 10.0),
+            // Bought at 10 with a target of 12, not reached yet.
+            candle(1, 10.0, 11.0, 11.0),
+            // Target reached, sold at 12.
+            candle(2, 11.0, 12.5, 12.0),
+            // Prediction below the open, no trade.
+            candle(3, 12.0, 13.0, 12.5),
+            // Bought at 12.5, never sold.
+            candle(4, 12.5, 12.6, 12.5),
+        ];
+        let predictions = [0.0, 12.0, 0.0, 11.0, 15.0];
+
+        let report = backtest
+            .simulate(&candles, |i| Ok(Some(predictions[i])))
+            .unwrap();
+
+        assert_eq!(report.trades.len(), 1);
+        let trade = &report.trades[0];
+        assert_eq!(trade.entry_time, 1);
+        assert_eq!(trade.exit_time, 2);
+        assert_eq!(trade.exit_price, 12.0);
+        assert_eq!(trade.profit, 20.0);
+        assert_eq!(trade.reason, ExitReason::Target);
+        assert_eq!(report.open_trade.as_ref().unwrap().entry_price, 12.5);
+        assert_eq!(report.final_equity, 1000.0 + 20.0);
+    }
+
+    #[test]
+    fn test_simulate_with_fee() {
+        let mut config = try_load_config("config.example.yaml");
+        config.trade.amount = 100.0;
+        config.backtest.fee = 0.01;
+        config.backtest.warmup = 1;
+        let model = Model::new();
+        let backtest = Backtest::new(&config, &model);
+
+        let candles = vec![candle(0, 10.0, 10.0, 10.0), candle(1, 10.0, 20.0, 10.0)];
+
+        let report = backtest.simulate(&candles, |_| Ok(Some(20.0))).unwrap();
+
+        // 9.9 bought after fees, sold for 198 minus 1.98 in fees.
+        assert_eq!(report.trades.len(), 1);
+        assert!((report.trades[0].quantity - 9.9).abs() < 1e-9);
+        assert!((report.trades[0].profit - (198.0 - 1.98 - 100.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_simulate_with_exits() {
+        let mut config = try_load_config("config.example.yaml");
+        config.trade.amount = 100.0;
+        config.trade.stop_loss = Some(10.0);
+        config.trade.max_holding_candles = Some(2);
+        config.backtest.fee = 0.0;
+        config.backtest.warmup = 1;
+        let model = Model::new();
+        let backtest = Backtest::new(&config, &model);
+
+        let candles = vec![
+            candle(0, 10.0, 10.0, 10.0),
+            // Bought at 10, dips to the stop-loss at 9.
+            candle(1, 10.0, 10.5, 8.5),
+            // Bought at 8.5, held for two candles.
+            candle(2, 8.5, 8.6, 8.5),
+            candle(3, 8.5, 8.6, 8.5),
+            // Sold at the open after the maximum holding time.
+            candle(4, 8.0, 8.6, 8.5),
+        ];
+
+        let report = backtest.simulate(&candles, |_| Ok(Some(20.0))).unwrap();
+
+        assert_eq!(report.trades.len(), 2);
+        assert_eq!(report.trades[0].reason, ExitReason::StopLoss);
+        assert_eq!(report.trades[0].exit_price, 9.0);
+        assert_eq!(report.trades[1].reason, ExitReason::MaxHoldingTime);
+        assert_eq!(report.trades[1].exit_time, 4);
+        assert_eq!(report.trades[1].exit_price, 8.0);
+    }
+
+    #[test]
+    fn test_simulate_not_enough_candles() {
+        let config = try_load_config("config.example.yaml");
+        let model = Model::new();
+        let backtest = Backtest::new(&config, &model);
+        assert!(backtest.simulate(&[], |_| Ok(Some(0.0))).is_err());
+    }
+
+    #[test]
+    fn test_run_warmup_too_short() {
+        let mut config = try_load_config("config.example.yaml");
+        config.backtest.warmup = config.dataset.features.warmup() + config.dataset.label.horizon();
+        let model = Model::new();
+        let candles = (0..100)
+            .map(|i| candle(i * 3600000, 10.0, 11.0, 10.0))
+            .collect::<Vec<_>>();
+
+        let err = Backtest::new(&config, &model).run(&candles).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("backtest.warmup must be longer"));
+    }
+}
@@ -1,12 +1,29 @@
-use crate::{config::BinanceConfig, market::Market};
+use crate::{
+    config::BinanceConfig,
+    market::{Balance, Market, OcoOrder, Order, OrderFill, OrderSide, OrderStatus, OrderType},
+    rate_limiter::{Endpoint, RateLimiter, SystemClock},
+    retry::RetryPolicy,
+    shutdown::Shutdown,
+    symbol_filters::SymbolFilters,
+    utils::{civil_from_days, days_from_civil},
+};
 use binance::{
-    account::Account,
-    api::Binance as BinanceApi,
+    account::{Account, TimeInForce},
+    api::{Binance as BinanceApi, Spot, API},
+    general::General,
     market::Market as Market_,
-    model::{KlineSummaries, KlineSummary},
+    model::{KlineSummaries, KlineSummary, Order as BinanceOrder, Transaction},
+    util::build_signed_request,
 };
 use genawaiter::rc::{Co, Gen};
-use std::{future::Future, time::Duration};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 /// The maximum amount of Klines binance supports in the response body.
 pub const BINANCE_MAX_KLINES: u16 = 1500;
@@ -18,6 +35,13 @@ pub const BINANCE_MARKET_EPOCH: u64 = 1502942400000;
 pub struct BinanceMarket {
     market: Market_,
     account: Account,
+    general: General,
+    retry: RetryPolicy,
+    limiter: Arc<RateLimiter>,
+    /// Interrupts the waits for retries and the rate limit.
+    shutdown: Arc<Shutdown>,
+    /// Exchange filters by symbol, loaded once.
+    filters: Mutex<HashMap<String, SymbolFilters>>,
 }
 
 pub struct BinanceKlineOptions {
@@ -25,7 +49,6 @@ pub struct BinanceKlineOptions {
     pub pair: String,
 
     /// Candle open/close intervals.
-    /// Only supports hourly and daily intervals for now.
     pub interval: BinanceKlineInterval,
 
     /// Maximum amount of results to return.
@@ -42,32 +65,183 @@ pub struct BinanceKlineOptions {
     pub end: Option<u64>,
 }
 
-#[allow(dead_code)]
+/// All kline intervals supported by binance.
+/// Every interval is aligned to UTC: weekly candles open on monday and monthly candles on the first day of the month.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
 pub enum BinanceKlineInterval {
+    OneMinute,
+    ThreeMinutes,
+    FiveMinutes,
+    FifteenMinutes,
+    ThirtyMinutes,
+    #[default]
     Hourly,
+    TwoHours,
+    FourHours,
+    SixHours,
+    EightHours,
+    TwelveHours,
     Daily,
+    ThreeDays,
+    Weekly,
+    Monthly,
 }
 
 impl ToString for BinanceKlineInterval {
     fn to_string(&self) -> String {
         String::from(match self {
+            BinanceKlineInterval::OneMinute => "1m",
+            BinanceKlineInterval::ThreeMinutes => "3m",
+            BinanceKlineInterval::FiveMinutes => "5m",
+            BinanceKlineInterval::FifteenMinutes => "15m",
+            BinanceKlineInterval::ThirtyMinutes => "30m",
             BinanceKlineInterval::Hourly => "1h",
+            BinanceKlineInterval::TwoHours => "2h",
+            BinanceKlineInterval::FourHours => "4h",
+            BinanceKlineInterval::SixHours => "6h",
+            BinanceKlineInterval::EightHours => "8h",
+            BinanceKlineInterval::TwelveHours => "12h",
             BinanceKlineInterval::Daily => "1d",
+            BinanceKlineInterval::ThreeDays => "3d",
+            BinanceKlineInterval::Weekly => "1w",
+            BinanceKlineInterval::Monthly => "1M",
         })
     }
 }
 
+impl FromStr for BinanceKlineInterval {
+    type Err = anyhow::Error;
+
+    fn from_str(interval: &str) -> Result<Self, Self::Err> {
+        BINANCE_KLINE_INTERVALS
+            .iter()
+            .find(|candidate| candidate.to_string() == interval)
+            .copied()
+            .ok_or(anyhow::anyhow!(
+                "unsupported kline interval {}, expected one of 1m, 3m, 5m, 15m, 30m, 1h, 2h, 4h, 6h, 8h, 12h, 1d, 3d, 1w or 1M",
+                interval
+            ))
+    }
+}
+
+impl TryFrom<String> for BinanceKlineInterval {
+    type Error = anyhow::Error;
+
+    fn try_from(interval: String) -> Result<Self, Self::Error> {
+        interval.parse()
+    }
+}
+
+const BINANCE_KLINE_INTERVALS: [BinanceKlineInterval; 15] = [
+    BinanceKlineInterval::OneMinute,
+    BinanceKlineInterval::ThreeMinutes,
+    BinanceKlineInterval::FiveMinutes,
+    BinanceKlineInterval::FifteenMinutes,
+    BinanceKlineInterval::ThirtyMinutes,
+    BinanceKlineInterval::Hourly,
+    BinanceKlineInterval::TwoHours,
+    BinanceKlineInterval::FourHours,
+    BinanceKlineInterval::SixHours,
+    BinanceKlineInterval::EightHours,
+    BinanceKlineInterval::TwelveHours,
+    BinanceKlineInterval::Daily,
+    BinanceKlineInterval::ThreeDays,
+    BinanceKlineInterval::Weekly,
+    BinanceKlineInterval::Monthly,
+];
+
+/// Milliseconds in a day.
+const DAY: u64 = 86_400_000;
+
 impl BinanceKlineInterval {
-    fn to_seconds(&self) -> u64 {
-        match self {
-            BinanceKlineInterval::Hourly => 3600,
-            BinanceKlineInterval::Daily => 86400,
+    /// Returns the length of a candle, or `None` for monthly candles whose length varies.
+    pub fn duration(&self) -> Option<Duration> {
+        let minutes = match self {
+            BinanceKlineInterval::OneMinute => 1,
+            BinanceKlineInterval::ThreeMinutes => 3,
+            BinanceKlineInterval::FiveMinutes => 5,
+            BinanceKlineInterval::FifteenMinutes => 15,
+            BinanceKlineInterval::ThirtyMinutes => 30,
+            BinanceKlineInterval::Hourly => 60,
+            BinanceKlineInterval::TwoHours => 2 * 60,
+            BinanceKlineInterval::FourHours => 4 * 60,
+            BinanceKlineInterval::SixHours => 6 * 60,
+            BinanceKlineInterval::EightHours => 8 * 60,
+            BinanceKlineInterval::TwelveHours => 12 * 60,
+            BinanceKlineInterval::Daily => 24 * 60,
+            BinanceKlineInterval::ThreeDays => 3 * 24 * 60,
+            BinanceKlineInterval::Weekly => 7 * 24 * 60,
+            BinanceKlineInterval::Monthly => return None,
+        };
+        Some(Duration::from_secs(minutes * 60))
+    }
+
+    /// Returns the number of the candle the timestamp (in milliseconds) falls in, counted from the unix epoch.
+    fn index(&self, timestamp: u64) -> u64 {
+        match self.duration() {
+            Some(duration) => {
+                // The unix epoch was a thursday, while weekly candles start on monday.
+                let offset = if *self == BinanceKlineInterval::Weekly {
+                    4 * DAY
+                } else {
+                    0
+                };
+                timestamp.saturating_sub(offset) / duration.as_millis() as u64
+            }
+            None => {
+                let (year, month, _) = civil_from_days((timestamp / DAY) as i64);
+                (year as u64 - 1970) * 12 + (month as u64 - 1)
+            }
+        }
+    }
+
+    /// Returns the open time (in milliseconds) of the candle with the given number.
+    fn start(&self, index: u64) -> u64 {
+        match self.duration() {
+            Some(duration) => {
+                let offset = if *self == BinanceKlineInterval::Weekly {
+                    4 * DAY
+                } else {
+                    0
+                };
+                index * duration.as_millis() as u64 + offset
+            }
+            None => {
+                let year = 1970 + (index / 12) as i64;
+                let month = (index % 12) as u32 + 1;
+                days_from_civil(year, month, 1) as u64 * DAY
+            }
         }
     }
+
+    /// Returns the open time of the candle the timestamp falls in.
+    pub fn floor(&self, timestamp: Duration) -> Duration {
+        let timestamp = timestamp.as_millis() as u64;
+        Duration::from_millis(self.start(self.index(timestamp)))
+    }
+
+    /// Returns the open time of the candle after the one the timestamp falls in.
+    pub fn next(&self, timestamp: Duration) -> Duration {
+        let timestamp = timestamp.as_millis() as u64;
+        Duration::from_millis(self.start(self.index(timestamp) + 1))
+    }
+
+    /// Returns the open time of the candle `count` candles before the one the timestamp falls in.
+    pub fn earlier(&self, timestamp: Duration, count: u64) -> Duration {
+        let timestamp = timestamp.as_millis() as u64;
+        Duration::from_millis(self.start(self.index(timestamp).saturating_sub(count)))
+    }
+
+    /// Returns how many candles opened after `from` up to and including the one `to` falls in.
+    pub fn candles_between(&self, from: Duration, to: Duration) -> u64 {
+        self.index(to.as_millis() as u64)
+            .saturating_sub(self.index(from.as_millis() as u64))
+    }
 }
 
 impl BinanceMarket {
-    pub fn new(config: BinanceConfig) -> Self {
+    pub fn new(config: BinanceConfig, shutdown: Arc<Shutdown>) -> Self {
         let market: Market_ = BinanceApi::new(
             Some(config.api_key.clone()),
             Some(config.api_secret.clone()),
@@ -76,28 +250,78 @@ impl BinanceMarket {
             Some(config.api_key.clone()),
             Some(config.api_secret.clone()),
         );
-        BinanceMarket { market, account }
+        let general: General = BinanceApi::new(None, None);
+        BinanceMarket {
+            market,
+            account,
+            general,
+            retry: config.retry,
+            limiter: Arc::new(RateLimiter::new(
+                config.weight_limit,
+                Box::new(SystemClock(shutdown.clone())),
+            )),
+            shutdown,
+            filters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the trading rules of a symbol, loading them from the exchange info the first time.
+    pub fn symbol_filters(&self, symbol: &str) -> anyhow::Result<SymbolFilters> {
+        if let Some(filters) = self.filters.lock().unwrap().get(symbol) {
+            return Ok(filters.clone());
+        }
+
+        let info = self.retry.retry(
+            &format!("fetching the exchange info of {}", symbol),
+            &self.shutdown,
+            || {
+                self.limiter
+                    .acquire(Endpoint::ExchangeInfo)
+                    .map_err(map_interrupted)?;
+                self.general.get_symbol_info(symbol)
+            },
+        )?;
+        let filters = SymbolFilters::from_binance(&info)?;
+        debug!("Loaded exchange filters: {:?}.", filters);
+
+        self.filters
+            .lock()
+            .unwrap()
+            .insert(symbol.to_string(), filters.clone());
+        Ok(filters)
     }
 
     /// Generator that returns klines from binance.
     /// Defaults to ALL klines from `BINANCE_MARKET_EPOCH` until now.
+    /// Failed requests are retried according to the retry policy. When they keep failing, the error is returned
+    /// as the last item, so callers can tell an incomplete history apart from a complete one.
     pub fn get_klines(
         &self,
         options: BinanceKlineOptions,
-    ) -> genawaiter::rc::Gen<KlineSummary, (), impl Future<Output = ()>> {
+    ) -> genawaiter::rc::Gen<anyhow::Result<KlineSummary>, (), impl Future<Output = ()>> {
         let market = self.market.clone();
+        let retry = self.retry.clone();
+        let limiter = self.limiter.clone();
+        let shutdown = self.shutdown.clone();
 
-        Gen::new(|co: Co<KlineSummary>| async move {
+        Gen::new(|co: Co<anyhow::Result<KlineSummary>>| async move {
             let mut start_time: Option<u64> = options.start;
 
             loop {
-                match market.get_klines(
-                    &options.pair,
-                    options.interval.to_string(),
-                    options.limit.or(Some(BINANCE_MAX_KLINES)),
-                    start_time,
-                    options.end,
-                ) {
+                let description = format!("fetching {} klines from {:?}", options.pair, start_time);
+                let limit = options.limit.unwrap_or(BINANCE_MAX_KLINES);
+                match retry.retry(&description, &shutdown, || {
+                    limiter
+                        .acquire(Endpoint::Klines { limit })
+                        .map_err(map_interrupted)?;
+                    market.get_klines(
+                        &options.pair,
+                        options.interval.to_string(),
+                        limit,
+                        start_time,
+                        options.end,
+                    )
+                }) {
                     Ok(klines) => match klines {
                         KlineSummaries::AllKlineSummaries(klines) => {
                             if klines.len() == 0 {
@@ -107,18 +331,15 @@ impl BinanceMarket {
                             let cursor = klines.last().unwrap().clone();
 
                             for kline in klines {
-                                co.yield_(kline).await;
+                                co.yield_(Ok(kline)).await;
                             }
 
-                            start_time = Some(
-                                cursor.close_time as u64
-                                    + Duration::from_secs(options.interval.to_seconds()).as_millis()
-                                        as u64,
-                            );
+                            // The next candle opens right after the last one closed.
+                            start_time = Some(cursor.close_time as u64 + 1);
                         }
                     },
                     Err(err) => {
-                        println!("error during retrieval of historical klines: {}", err);
+                        co.yield_(Err(err)).await;
                         break;
                     }
                 };
@@ -129,38 +350,564 @@ impl BinanceMarket {
 
 impl Market for BinanceMarket {
     fn get_price(&self, symbol: &str) -> anyhow::Result<f64> {
-        let price_symbol = self.market.get_price(symbol).map_err(map_binance_error)?;
+        let price_symbol = self.retry.retry(
+            &format!("fetching the price of {}", symbol),
+            &self.shutdown,
+            || {
+                self.limiter
+                    .acquire(Endpoint::Price)
+                    .map_err(map_interrupted)?;
+                self.market.get_price(symbol)
+            },
+        )?;
         Ok(price_symbol.price)
     }
 
-    fn place_buy_order(&self, symbol: &str, quantity: f64, test: bool) -> anyhow::Result<()> {
+    fn place_buy_order(
+        &self,
+        symbol: &str,
+        quantity: f64,
+        test: bool,
+    ) -> anyhow::Result<OrderFill> {
+        let quantity = self.symbol_filters(symbol)?.market_buy_amount(quantity)?;
+
+        self.limiter.acquire(Endpoint::Order)?;
         if test {
             self.account
                 .test_market_buy_using_quote_quantity(symbol, quantity)
-                .map_err(map_binance_error)
+                .map_err(map_binance_error)?;
+
+            // The test endpoint doesn't execute anything, so estimate the fill at the current price.
+            let price = self.get_price(symbol)?;
+            Ok(OrderFill {
+                order_id: 0,
+                quantity: quantity / price,
+                price,
+                commission: 0.0,
+                commission_asset: String::new(),
+            })
         } else {
             self.account
                 .market_buy_using_quote_quantity(symbol, quantity)
-                .map(|_| ())
+                .map(|transaction| map_transaction(symbol, transaction))
                 .map_err(map_binance_error)
         }
     }
 
-    fn place_sell_order(&self, symbol: &str, quantity: f64, test: bool) -> anyhow::Result<()> {
+    fn place_sell_order(
+        &self,
+        symbol: &str,
+        quantity: f64,
+        test: bool,
+    ) -> anyhow::Result<OrderFill> {
+        let price = self.get_price(symbol)?;
+        let quantity = self
+            .symbol_filters(symbol)?
+            .market_sell_quantity(quantity, price)?;
+
+        self.limiter.acquire(Endpoint::Order)?;
         if test {
             self.account
-                .test_market_sell_using_quote_quantity(symbol, quantity)
-                .map_err(map_binance_error)
+                .test_market_sell(symbol, quantity)
+                .map_err(map_binance_error)?;
+
+            Ok(OrderFill {
+                order_id: 0,
+                quantity,
+                price,
+                commission: 0.0,
+                commission_asset: String::new(),
+            })
         } else {
             self.account
-                .market_sell_using_quote_quantity(symbol, quantity)
-                .map(|_| ())
+                .market_sell(symbol, quantity)
+                .map(|transaction| map_transaction(symbol, transaction))
                 .map_err(map_binance_error)
         }
     }
+
+    fn place_limit_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        price: f64,
+        test: bool,
+    ) -> anyhow::Result<Order> {
+        let (quantity, price) = self.symbol_filters(symbol)?.limit_order(quantity, price)?;
+
+        self.limiter.acquire(Endpoint::Order)?;
+        if test {
+            match side {
+                OrderSide::Buy => self.account.test_limit_buy(symbol, quantity, price),
+                OrderSide::Sell => self.account.test_limit_sell(symbol, quantity, price),
+            }
+            .map_err(map_binance_error)?;
+            return Ok(test_order(
+                symbol,
+                side,
+                OrderType::Limit,
+                quantity,
+                price,
+                None,
+            ));
+        }
+
+        let transaction = match side {
+            OrderSide::Buy => self.account.limit_buy(symbol, quantity, price),
+            OrderSide::Sell => self.account.limit_sell(symbol, quantity, price),
+        }
+        .map_err(map_binance_error)?;
+        map_transaction_order(transaction, None)
+    }
+
+    fn place_stop_limit_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        stop_price: f64,
+        price: f64,
+        test: bool,
+    ) -> anyhow::Result<Order> {
+        let filters = self.symbol_filters(symbol)?;
+        let (quantity, price) = filters.limit_order(quantity, price)?;
+        let stop_price = filters.round_price(stop_price);
+        filters.check_price(stop_price)?;
+
+        self.limiter.acquire(Endpoint::Order)?;
+        if test {
+            match side {
+                OrderSide::Buy => self.account.test_stop_limit_buy_order(
+                    symbol,
+                    quantity,
+                    price,
+                    stop_price,
+                    TimeInForce::GTC,
+                ),
+                OrderSide::Sell => self.account.test_stop_limit_sell_order(
+                    symbol,
+                    quantity,
+                    price,
+                    stop_price,
+                    TimeInForce::GTC,
+                ),
+            }
+            .map_err(map_binance_error)?;
+            return Ok(test_order(
+                symbol,
+                side,
+                OrderType::StopLossLimit,
+                quantity,
+                price,
+                Some(stop_price),
+            ));
+        }
+
+        let transaction = match side {
+            OrderSide::Buy => self.account.stop_limit_buy_order(
+                symbol,
+                quantity,
+                price,
+                stop_price,
+                TimeInForce::GTC,
+            ),
+            OrderSide::Sell => self.account.stop_limit_sell_order(
+                symbol,
+                quantity,
+                price,
+                stop_price,
+                TimeInForce::GTC,
+            ),
+        }
+        .map_err(map_binance_error)?;
+        map_transaction_order(transaction, Some(stop_price))
+    }
+
+    fn place_oco_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        price: f64,
+        stop_price: f64,
+        stop_limit_price: f64,
+    ) -> anyhow::Result<OcoOrder> {
+        let filters = self.symbol_filters(symbol)?;
+        let (quantity, price) = filters.limit_order(quantity, price)?;
+        let (_, stop_limit_price) = filters.limit_order(quantity, stop_limit_price)?;
+        let stop_price = filters.round_price(stop_price);
+        filters.check_price(stop_price)?;
+
+        // binance-rs doesn't support OCO orders, so the request is signed and sent with its client directly.
+        let mut parameters: BTreeMap<String, String> = BTreeMap::new();
+        parameters.insert("symbol".into(), symbol.into());
+        parameters.insert("side".into(), side_name(side).into());
+        parameters.insert("quantity".into(), quantity.to_string());
+        parameters.insert("price".into(), price.to_string());
+        parameters.insert("stopPrice".into(), stop_price.to_string());
+        parameters.insert("stopLimitPrice".into(), stop_limit_price.to_string());
+        parameters.insert("stopLimitTimeInForce".into(), "GTC".into());
+        let request = build_signed_request(parameters, self.account.recv_window)
+            .map_err(map_binance_error)?;
+
+        self.limiter.acquire(Endpoint::Order)?;
+        let response: OcoResponse = self
+            .account
+            .client
+            .post_signed(API::Spot(Spot::Oco), request)
+            .map_err(map_binance_error)?;
+
+        let mut limit = None;
+        let mut stop = None;
+        for report in response.order_reports {
+            let order = report.into_order(symbol)?;
+            if order.order_type == OrderType::LimitMaker {
+                limit = Some(order);
+            } else {
+                stop = Some(order);
+            }
+        }
+
+        Ok(OcoOrder {
+            id: response.order_list_id,
+            limit: limit.ok_or(anyhow::anyhow!(
+                "OCO order {} has no limit order",
+                response.order_list_id
+            ))?,
+            stop: stop.ok_or(anyhow::anyhow!(
+                "OCO order {} has no stop order",
+                response.order_list_id
+            ))?,
+        })
+    }
+
+    fn get_order(&self, symbol: &str, id: u64) -> anyhow::Result<Order> {
+        let order = self.retry.retry(
+            &format!("fetching order {} of {}", id, symbol),
+            &self.shutdown,
+            || {
+                self.limiter
+                    .acquire(Endpoint::OrderStatus)
+                    .map_err(map_interrupted)?;
+                self.account.order_status(symbol, id)
+            },
+        )?;
+        map_order(order)
+    }
+
+    fn cancel_order(&self, symbol: &str, id: u64) -> anyhow::Result<Order> {
+        self.limiter.acquire(Endpoint::Order)?;
+        self.account
+            .cancel_order(symbol, id)
+            .map_err(map_binance_error)?;
+
+        // The cancel response doesn't say how much was filled before, so fetch the final state.
+        self.get_order(symbol, id)
+    }
+
+    fn open_orders(&self, symbol: &str) -> anyhow::Result<Vec<Order>> {
+        self.retry
+            .retry(
+                &format!("fetching the open orders of {}", symbol),
+                &self.shutdown,
+                || {
+                    self.limiter
+                        .acquire(Endpoint::OpenOrders)
+                        .map_err(map_interrupted)?;
+                    self.account.get_open_orders(symbol)
+                },
+            )?
+            .into_iter()
+            .map(map_order)
+            .collect()
+    }
+
+    fn get_balance(&self, asset: &str) -> anyhow::Result<Balance> {
+        Ok(self
+            .get_balances()?
+            .into_iter()
+            .find(|balance| balance.asset == asset)
+            .unwrap_or(Balance {
+                asset: asset.to_string(),
+                free: 0.0,
+                locked: 0.0,
+            }))
+    }
+
+    fn get_balances(&self) -> anyhow::Result<Vec<Balance>> {
+        let account = self
+            .retry
+            .retry("fetching the account balances", &self.shutdown, || {
+                self.limiter
+                    .acquire(Endpoint::Account)
+                    .map_err(map_interrupted)?;
+                self.account.get_account()
+            })?;
+
+        let mut balances = Vec::new();
+        for balance in account.balances {
+            let balance = Balance {
+                free: balance.free.parse()?,
+                locked: balance.locked.parse()?,
+                asset: balance.asset,
+            };
+            if balance.total() > 0.0 {
+                balances.push(balance);
+            }
+        }
+        Ok(balances)
+    }
+}
+
+/// Response of `POST /api/v3/order/oco`, only the fields the bot uses.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OcoResponse {
+    order_list_id: u64,
+    order_reports: Vec<OcoOrderReport>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OcoOrderReport {
+    order_id: u64,
+    price: String,
+    orig_qty: String,
+    executed_qty: String,
+    cummulative_quote_qty: String,
+    status: String,
+    #[serde(rename = "type")]
+    type_name: String,
+    side: String,
+    stop_price: Option<String>,
+}
+
+impl OcoOrderReport {
+    fn into_order(self, symbol: &str) -> anyhow::Result<Order> {
+        Ok(Order {
+            id: self.order_id,
+            symbol: symbol.to_string(),
+            side: parse_side(&self.side)?,
+            order_type: parse_order_type(&self.type_name)?,
+            status: parse_order_status(&self.status)?,
+            price: self.price.parse()?,
+            stop_price: self.stop_price.map(|price| price.parse()).transpose()?,
+            quantity: self.orig_qty.parse()?,
+            executed_quantity: self.executed_qty.parse()?,
+            quote_quantity: self.cummulative_quote_qty.parse()?,
+        })
+    }
+}
+
+/// Converts an executed binance order to an `OrderFill`.
+fn map_transaction(symbol: &str, transaction: Transaction) -> OrderFill {
+    let fills = transaction.fills.unwrap_or_default();
+    let commission_asset = fills
+        .first()
+        .map(|fill| fill.commission_asset.clone())
+        .unwrap_or_default();
+    let commission: f64 = fills
+        .iter()
+        .filter(|fill| fill.commission_asset == commission_asset)
+        .map(|fill| fill.commission)
+        .sum();
+
+    let price = if transaction.executed_qty > 0.0 {
+        transaction.cummulative_quote_qty / transaction.executed_qty
+    } else {
+        transaction.price
+    };
+
+    // When buying, binance deducts the commission from the received base asset unless it was paid with another asset (e.g. BNB).
+    let quantity = if transaction.side == "BUY"
+        && !commission_asset.is_empty()
+        && symbol.starts_with(&commission_asset)
+    {
+        transaction.executed_qty - commission
+    } else {
+        transaction.executed_qty
+    };
+
+    OrderFill {
+        order_id: transaction.order_id,
+        quantity,
+        price,
+        commission,
+        commission_asset,
+    }
+}
+
+/// Converts a placed binance order to an `Order`.
+fn map_transaction_order(
+    transaction: Transaction,
+    stop_price: Option<f64>,
+) -> anyhow::Result<Order> {
+    Ok(Order {
+        id: transaction.order_id,
+        symbol: transaction.symbol,
+        side: parse_side(&transaction.side)?,
+        order_type: parse_order_type(&transaction.type_name)?,
+        status: parse_order_status(&transaction.status)?,
+        price: transaction.price,
+        stop_price,
+        quantity: transaction.orig_qty,
+        executed_quantity: transaction.executed_qty,
+        quote_quantity: transaction.cummulative_quote_qty,
+    })
+}
+
+/// Converts a queried binance order to an `Order`.
+fn map_order(order: BinanceOrder) -> anyhow::Result<Order> {
+    Ok(Order {
+        id: order.order_id,
+        side: parse_side(&order.side)?,
+        order_type: parse_order_type(&order.type_name)?,
+        status: parse_order_status(&order.status)?,
+        price: order.price,
+        stop_price: if order.stop_price > 0.0 {
+            Some(order.stop_price)
+        } else {
+            None
+        },
+        quantity: order.orig_qty.parse()?,
+        executed_quantity: order.executed_qty.parse()?,
+        quote_quantity: order.cummulative_quote_qty.parse()?,
+        symbol: order.symbol,
+    })
+}
+
+/// The binance test endpoints only validate orders, so this is what they would have looked like.
+fn test_order(
+    symbol: &str,
+    side: OrderSide,
+    order_type: OrderType,
+    quantity: f64,
+    price: f64,
+    stop_price: Option<f64>,
+) -> Order {
+    Order {
+        id: 0,
+        symbol: symbol.to_string(),
+        side,
+        order_type,
+        status: OrderStatus::New,
+        price,
+        stop_price,
+        quantity,
+        executed_quantity: 0.0,
+        quote_quantity: 0.0,
+    }
+}
+
+fn side_name(side: OrderSide) -> &'static str {
+    match side {
+        OrderSide::Buy => "BUY",
+        OrderSide::Sell => "SELL",
+    }
+}
+
+fn parse_side(side: &str) -> anyhow::Result<OrderSide> {
+    match side {
+        "BUY" => Ok(OrderSide::Buy),
+        "SELL" => Ok(OrderSide::Sell),
+        side => Err(anyhow::anyhow!("unknown order side {}", side)),
+    }
+}
+
+fn parse_order_type(order_type: &str) -> anyhow::Result<OrderType> {
+    match order_type {
+        "MARKET" => Ok(OrderType::Market),
+        "LIMIT" => Ok(OrderType::Limit),
+        "STOP_LOSS" => Ok(OrderType::StopLoss),
+        "STOP_LOSS_LIMIT" => Ok(OrderType::StopLossLimit),
+        "TAKE_PROFIT" => Ok(OrderType::TakeProfit),
+        "TAKE_PROFIT_LIMIT" => Ok(OrderType::TakeProfitLimit),
+        "LIMIT_MAKER" => Ok(OrderType::LimitMaker),
+        order_type => Err(anyhow::anyhow!("unknown order type {}", order_type)),
+    }
+}
+
+fn parse_order_status(status: &str) -> anyhow::Result<OrderStatus> {
+    match status {
+        "NEW" => Ok(OrderStatus::New),
+        "PARTIALLY_FILLED" => Ok(OrderStatus::PartiallyFilled),
+        "FILLED" => Ok(OrderStatus::Filled),
+        "CANCELED" | "PENDING_CANCEL" => Ok(OrderStatus::Canceled),
+        "REJECTED" => Ok(OrderStatus::Rejected),
+        "EXPIRED" | "EXPIRED_IN_MATCH" => Ok(OrderStatus::Expired),
+        status => Err(anyhow::anyhow!("unknown order status {}", status)),
+    }
+}
+
+/// Turns an interrupted wait for the rate limit into a binance error, which isn't retried.
+fn map_interrupted(err: anyhow::Error) -> binance::errors::Error {
+    binance::errors::Error::from(binance::errors::ErrorKind::Msg(err.to_string()))
 }
 
 /// Converts a binance error to an anyhow error.
 fn map_binance_error(err: binance::errors::Error) -> anyhow::Error {
     anyhow::anyhow!(err.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::BinanceKlineInterval;
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(
+            "15m".parse::<BinanceKlineInterval>().unwrap(),
+            BinanceKlineInterval::FifteenMinutes
+        );
+        assert_eq!(
+            "1M".parse::<BinanceKlineInterval>().unwrap(),
+            BinanceKlineInterval::Monthly
+        );
+        assert_eq!(BinanceKlineInterval::Monthly.to_string(), "1M");
+        assert!("1y".parse::<BinanceKlineInterval>().is_err());
+    }
+
+    #[test]
+    fn test_interval_boundaries() {
+        let timestamp = Duration::from_millis(1674940162060); // 2023-01-28T21:09:22.060Z (saturday)
+
+        let interval = BinanceKlineInterval::FifteenMinutes;
+        assert_eq!(interval.floor(timestamp).as_millis(), 1674939600000); // 21:00
+        assert_eq!(interval.next(timestamp).as_millis(), 1674940500000); // 21:15
+        assert_eq!(
+            interval.earlier(timestamp, 4).as_millis(),
+            1674936000000 // 20:00
+        );
+
+        let interval = BinanceKlineInterval::FourHours;
+        assert_eq!(interval.floor(timestamp).as_millis(), 1674936000000); // 20:00
+        assert_eq!(interval.next(timestamp).as_millis(), 1674950400000); // 2023-01-29T00:00
+
+        let interval = BinanceKlineInterval::Weekly;
+        assert_eq!(interval.floor(timestamp).as_millis(), 1674432000000); // monday 2023-01-23
+        assert_eq!(interval.next(timestamp).as_millis(), 1675036800000); // monday 2023-01-30
+
+        let interval = BinanceKlineInterval::Monthly;
+        assert_eq!(interval.floor(timestamp).as_millis(), 1672531200000); // 2023-01-01
+        assert_eq!(interval.next(timestamp).as_millis(), 1675209600000); // 2023-02-01
+        assert_eq!(
+            interval.earlier(timestamp, 13).as_millis(),
+            1638316800000 // 2021-12-01
+        );
+        assert_eq!(
+            interval.candles_between(Duration::from_millis(1638316800000), timestamp),
+            13
+        );
+
+        // A timestamp at the boundary belongs to the candle that opens there.
+        let boundary = Duration::from_millis(1675209600000);
+        assert_eq!(interval.floor(boundary), boundary);
+        assert_eq!(
+            BinanceKlineInterval::Hourly.candles_between(timestamp, timestamp),
+            0
+        );
+    }
+}
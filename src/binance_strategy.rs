@@ -1,189 +1,850 @@
 use crate::{
-    binance_market::{
-        BinanceKlineInterval, BinanceKlineOptions, BinanceMarket, BINANCE_MARKET_EPOCH,
-    },
-    dataset::DataSet,
-    market::Market,
+    binance_market::{BinanceKlineOptions, BinanceMarket},
+    config::TradeMode,
+    dataset::Candle,
+    events::{SkipReason, TradeEvent},
+    market::{Balance, Market, Order, OrderFill, OrderSide, OrderStatus},
+    notifications::NotificationSender,
+    position::{ExitReason, ExitRules, Position, PositionStore},
     strategy::{LightGBMStrategy, Strategy},
-    utils::{calculate_profit, ceil_hour, now},
+    utils::{calculate_profit, now},
 };
 use anyhow::anyhow;
 use binance::websockets::{WebSockets, WebsocketEvent};
-use std::{
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        mpsc::Sender,
-        Arc,
-    },
-    thread,
-};
+use std::{sync::atomic::Ordering, time::Duration};
+
+/// How long to wait before connecting to the websocket again after it failed.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
 
 impl LightGBMStrategy<BinanceMarket> {
-    /// Load dataset data (features, labels) from binance klines API.
-    fn load_dataset(&self) -> DataSet {
-        DataSet::from_binance(
+    /// Load the closed candles to train on from the kline store, after fetching the ones it's missing from binance.
+    /// That's the full history, unless a training window is configured.
+    fn load_candles(&self) -> anyhow::Result<Vec<Candle>> {
+        let now = now();
+        let added = self.klines.update(
             &self.market,
-            BinanceKlineOptions {
-                pair: self.config.symbol.clone(),
-                interval: BinanceKlineInterval::Hourly,
-                limit: None,
-                start: Some(BINANCE_MARKET_EPOCH),
-                end: None,
-            },
-        )
+            &self.config.symbol,
+            self.config.interval,
+            now.as_millis() as u64,
+        )?;
+        debug!("Stored {} new klines.", added);
+
+        let start = match self.config.training.window {
+            Some(window) => self.config.interval.earlier(now, window).as_millis() as u64,
+            None => 0,
+        };
+        self.klines
+            .read(&self.config.symbol, self.config.interval, start..)
     }
 
-    /// Get the current candle's `open` and `close` price.
-    fn get_current_candle(&self) -> anyhow::Result<(f64, f64)> {
+    /// Get the current candle, which is still forming.
+    fn get_current_candle(&self) -> anyhow::Result<Candle> {
         let kline = self
             .market
             .get_klines(BinanceKlineOptions {
                 pair: self.config.symbol.clone(),
-                interval: BinanceKlineInterval::Hourly,
+                interval: self.config.interval,
                 limit: Some(1),
                 start: None,
                 end: None,
             })
             .into_iter()
             .last()
-            .ok_or(anyhow!("failed to get current kline"))?;
-        let kline_open = kline.open.parse::<f64>().unwrap();
-        let kline_close = kline.close.parse::<f64>().unwrap();
-        Ok((kline_open, kline_close))
+            .ok_or(anyhow!("failed to get current kline"))??;
+        Ok(Candle::from(kline))
     }
 }
 
-impl Strategy for LightGBMStrategy<BinanceMarket> {
-    fn execute(&self, running: Arc<AtomicBool>, tx: &Sender<String>) {
-        while running.load(Ordering::SeqCst) {
-            let dataset = self.load_dataset();
-
-            info!(
-                "Last open, high in dataset: {}, {}",
-                dataset.0.last().unwrap()[0],
-                dataset.1.last().unwrap()
+impl LightGBMStrategy<BinanceMarket> {
+    /// Predicts with the latest model, retraining it when due, and buys when the predicted high is above the current price.
+    /// Returns `false` when the trade was skipped, after waiting for the next candle.
+    fn open_position(
+        &self,
+        positions: &mut PositionStore,
+        tx: &NotificationSender<TradeEvent>,
+    ) -> bool {
+        // Predict the price over the next candles from the last closed candle, using latest data from binance.
+        // Never train on an incomplete history, try again at the next candle instead.
+        let candles = match self.load_candles() {
+            Ok(candles) => candles,
+            Err(err) => {
+                self.report_error(
+                    tx,
+                    format!(
+                        "Failed to load klines, waiting for the next candle: {:#}",
+                        err
+                    ),
+                );
+                self.scheduler.wait_for_next_candle();
+                return false;
+            }
+        };
+        let score = match self.predict(&candles, tx) {
+            Ok(score) => score,
+            Err(err) => {
+                self.report_error(
+                    tx,
+                    format!("Failed to predict, waiting for the next candle: {:#}", err),
+                );
+                self.scheduler.wait_for_next_candle();
+                return false;
+            }
+        };
+
+        // Get the current price candle.
+        let current_candle = match self.get_current_candle() {
+            Ok(candle) => candle,
+            Err(err) => {
+                self.report_error(
+                    tx,
+                    format!(
+                        "Failed to get the current kline, waiting for the next candle: {:#}",
+                        err
+                    ),
+                );
+                self.scheduler.wait_for_next_candle();
+                return false;
+            }
+        };
+        let current_kline_open = current_candle.open;
+        let current_kline_close = current_candle.close;
+
+        let event = TradeEvent::Prediction {
+            symbol: self.config.symbol.clone(),
+            model: self.model_version().unwrap_or_default(),
+            label: self.config.dataset.label,
+            predicted: score,
+            open: current_kline_open,
+            close: current_kline_close,
+        };
+        info!("{}", event);
+        self.notify(tx, event);
+
+        // Wait until the next candle if the trade is not profitable according to our prediction.
+        if score < current_kline_open || score < current_kline_close {
+            self.skip_trade(
+                tx,
+                SkipReason::PredictionBelowPrice {
+                    predicted: score,
+                    open: current_kline_open,
+                    close: current_kline_close,
+                },
             );
-            // println!("{:?}", dataset);
+            return false;
+        }
 
-            // Train the model using latest data from binance.
-            let booster = self.train_model(dataset).unwrap();
+        // Test orders don't spend anything, so only real trades need the funds.
+        if !self.config.trade.test {
+            match self.quote_balance() {
+                Ok(balance) if balance.free < self.config.trade.amount => {
+                    self.skip_trade(
+                        tx,
+                        SkipReason::InsufficientFunds {
+                            asset: balance.asset,
+                            required: self.config.trade.amount,
+                            available: balance.free,
+                        },
+                    );
+                    return false;
+                }
+                Ok(_) => (),
+                Err(err) => {
+                    self.report_error(
+                        tx,
+                        format!(
+                            "Failed to check funds, skipping trade until the next candle: {:#}",
+                            err
+                        ),
+                    );
+                    self.scheduler.wait_for_next_candle();
+                    return false;
+                }
+            }
+        }
 
-            // Get the current price candle.
-            let (current_kline_open, current_kline_close) = self.get_current_candle().unwrap();
+        if self.config.trade.mode == TradeMode::Approve {
+            if let Err(reason) =
+                self.await_approval(tx, score, current_kline_open, current_kline_close)
+            {
+                self.skip_trade(tx, reason);
+                return false;
+            }
 
-            // Predict the next `high` price.
-            let prediction = booster.predict(vec![vec![current_kline_open]]).unwrap();
-            let score = prediction[0][0];
+            // The price may have moved while waiting, so make sure the prediction is still above it.
+            match self.market.get_price(&self.config.symbol) {
+                Ok(price) if price > score => {
+                    self.skip_trade(
+                        tx,
+                        SkipReason::PriceAboveTarget {
+                            predicted: score,
+                            price,
+                        },
+                    );
+                    return false;
+                }
+                Ok(_) => (),
+                Err(err) => {
+                    self.report_error(
+                        tx,
+                        format!(
+                            "Failed to check the price after the approval, skipping trade until the next candle: {:#}",
+                            err
+                        ),
+                    );
+                    self.scheduler.wait_for_next_candle();
+                    return false;
+                }
+            }
+        }
 
-            info!(
-                "Current kline open, close: {}, {}.",
-                current_kline_open, current_kline_close
-            );
-            info!("Predicted high: {}.", score);
+        // Place buy order
+        info!(
+            "Buying {} {}.",
+            self.config.trade.amount,
+            self.config.symbol.clone(),
+        );
+        if self
+            .transition(
+                positions,
+                tx,
+                Position::Buying {
+                    amount: self.config.trade.amount,
+                    target: score,
+                },
+            )
+            .is_err()
+        {
+            return false;
+        }
+        let buy_fill = match self.market.place_buy_order(
+            &self.config.symbol,
+            self.config.trade.amount,
+            self.config.trade.test,
+        ) {
+            Ok(fill) => fill,
+            Err(err) => {
+                // The order was rejected, so nothing was bought.
+                if self.transition(positions, tx, Position::Flat).is_err() {
+                    return false;
+                }
+                self.report_error(
+                    tx,
+                    format!(
+                        "Failed to place buy order, waiting for the next candle: {:#}",
+                        err
+                    ),
+                );
+                self.scheduler.wait_for_next_candle();
+                return false;
+            }
+        };
+        let opened_at = now().as_millis() as u64;
+        let saved = self.transition(
+            positions,
+            tx,
+            Position::Holding {
+                quantity: buy_fill.quantity,
+                price: buy_fill.price,
+                target: score,
+                opened_at,
+                highest_price: buy_fill.price,
+                take_profit_order: None,
+            },
+        );
+        self.report_fill(tx, OrderSide::Buy, &buy_fill);
+        if saved.is_err() {
+            return false;
+        }
 
-            // Wait until the next candle if the trade is not profitable according to our prediction.
-            if score < current_kline_open || score < current_kline_close {
-                let duration = ceil_hour(now()) - now();
-                warn!("Predicted value {} is lower than the open ({}) or current ({}) price, skipping trade and waiting {:?} until the start of the next candle.", score, current_kline_open, current_kline_close, duration);
-                thread::sleep(duration);
-                continue;
+        // Test orders never rest on the book, so the target is watched over the websocket instead.
+        if self.config.trade.take_profit_order && !self.config.trade.test {
+            match self.market.place_limit_order(
+                &self.config.symbol,
+                OrderSide::Sell,
+                buy_fill.quantity,
+                score,
+                false,
+            ) {
+                Ok(order) => {
+                    let event = TradeEvent::OrderPlaced {
+                        symbol: order.symbol.clone(),
+                        order_id: order.id,
+                        side: order.side,
+                        order_type: order.order_type,
+                        quantity: order.quantity,
+                        price: order.price,
+                    };
+                    info!("{}", event);
+                    self.notify(tx, event);
+                    if self
+                        .transition(
+                            positions,
+                            tx,
+                            Position::Holding {
+                                quantity: buy_fill.quantity,
+                                price: buy_fill.price,
+                                target: score,
+                                opened_at,
+                                highest_price: buy_fill.price,
+                                take_profit_order: Some(order.id),
+                            },
+                        )
+                        .is_err()
+                    {
+                        return false;
+                    }
+                }
+                Err(err) => {
+                    self.report_error(
+                        tx,
+                        format!(
+                            "Failed to place take-profit order, selling with a market order once the target is reached instead: {:#}",
+                            err
+                        ),
+                    );
+                }
             }
+        }
 
-            // Place buy order
-            info!(
-                "Buying {} {}.",
-                self.config.trade.amount,
-                self.config.symbol.clone(),
-            );
-            self.market
-                .place_buy_order(
-                    &self.config.symbol,
-                    self.config.trade.amount,
-                    self.config.trade.test,
-                )
-                .expect("failed to place buy order");
-            info!(
-                "Bought {} {}.",
-                self.config.trade.amount,
-                self.config.symbol.clone(),
+        true
+    }
+
+    /// Saves the new state of the position and shows it in the status.
+    /// When it can't be saved, the state file no longer matches the exchange, so the error is reported and trading stops.
+    fn transition(
+        &self,
+        positions: &mut PositionStore,
+        tx: &NotificationSender<TradeEvent>,
+        position: Position,
+    ) -> anyhow::Result<()> {
+        if let Err(err) = positions.transition(position.clone()) {
+            self.report_error(
+                tx,
+                format!(
+                    "Failed to save the position ({:?}), stopping. Check your binance account and fix {} before restarting: {:#}",
+                    position, self.config.trade.state_file, err
+                ),
             );
+            self.scheduler.stop();
+            return Err(err);
+        }
+        self.status.set_position(position);
+        Ok(())
+    }
 
-            // Wait and sell once the prediction has been reached.
-            // If the prediction hasn't been reached at the end of the candle, we wait until it is reached eventually.
-            // We never sell at a loss!
-            let connected = AtomicBool::new(true);
-            // TODO: handle binance's 24hr websocket connection timeout
-            let mut web_socket = WebSockets::new(|event: WebsocketEvent| {
-                // Disconnect if we got the signal to terminate the program (e.g. CTRL + C).
-                if running.load(Ordering::SeqCst) == false {
-                    connected.store(false, Ordering::SeqCst);
-                    return Ok(());
-                }
+    /// Returns the balance of the quote asset, which is spent on buying.
+    fn quote_balance(&self) -> anyhow::Result<Balance> {
+        let (_, quote_asset) = self.assets()?;
+        self.market.get_balance(&quote_asset)
+    }
+
+    /// Sends why no position is opened and waits for the next candle.
+    fn skip_trade(&self, tx: &NotificationSender<TradeEvent>, reason: SkipReason) {
+        let event = TradeEvent::TradeSkipped {
+            symbol: self.config.symbol.clone(),
+            reason,
+        };
+        let duration = self.scheduler.until_next_candle(now());
+        warn!(
+            "{} Waiting {:?} until the start of the next candle.",
+            event, duration
+        );
+        self.notify(tx, event);
+        self.scheduler.wait_for_next_candle();
+    }
+
+    /// Logs and sends an error the strategy recovers from, or is about to stop on.
+    fn report_error(&self, tx: &NotificationSender<TradeEvent>, message: String) {
+        error!("{}", message);
+        self.notify(tx, TradeEvent::Error { message });
+    }
+
+    /// Logs and sends a filled market order.
+    fn report_fill(&self, tx: &NotificationSender<TradeEvent>, side: OrderSide, fill: &OrderFill) {
+        let event = TradeEvent::OrderFilled {
+            symbol: self.config.symbol.clone(),
+            order_id: fill.order_id,
+            side,
+            quantity: fill.quantity,
+            price: fill.price,
+            commission: Some(fill.commission),
+            commission_asset: Some(fill.commission_asset.clone()),
+        };
+        info!("{}", event);
+        self.notify(tx, event);
+    }
+
+    /// Returns the base and quote asset of the traded symbol, as listed in its exchange info.
+    fn assets(&self) -> anyhow::Result<(String, String)> {
+        let filters = self.market.symbol_filters(&self.config.symbol)?;
+        Ok((filters.base_asset, filters.quote_asset))
+    }
+
+    /// Logs and sends a filled order that rested on the book.
+    fn report_filled_order(&self, tx: &NotificationSender<TradeEvent>, order: &Order) {
+        let event = TradeEvent::OrderFilled {
+            symbol: order.symbol.clone(),
+            order_id: order.id,
+            side: order.side,
+            quantity: order.executed_quantity,
+            price: order.average_price().unwrap_or(order.price),
+            commission: None,
+            commission_asset: None,
+        };
+        info!("{}", event);
+        self.notify(tx, event);
+    }
+
+    /// Sends the balances of the traded assets.
+    fn report_holdings(&self, tx: &NotificationSender<TradeEvent>) {
+        let holdings = self.assets().and_then(|(base_asset, quote_asset)| {
+            Ok((
+                self.market.get_balance(&base_asset)?,
+                self.market.get_balance(&quote_asset)?,
+            ))
+        });
+
+        match holdings {
+            Ok((base, quote)) => {
+                let event = TradeEvent::Holdings { base, quote };
+                info!("{}", event);
+                self.notify(tx, event);
+            }
+            Err(err) => warn!("Failed to fetch balances: {:#}", err),
+        }
+    }
+
+    /// Returns the take-profit order once it's completely filled.
+    fn filled_take_profit_order(&self, id: u64) -> Option<Order> {
+        match self.market.get_order(&self.config.symbol, id) {
+            Ok(order) if order.status == OrderStatus::Filled => Some(order),
+            Ok(_) => None,
+            Err(err) => {
+                warn!("Failed to check take-profit order {}: {:#}", id, err);
+                None
+            }
+        }
+    }
+
+    /// Closes the position the take-profit order sold.
+    fn close_take_profit(
+        &self,
+        positions: &mut PositionStore,
+        tx: &NotificationSender<TradeEvent>,
+        order: &Order,
+        initial_price: f64,
+    ) {
+        // The sale is reported even when it couldn't be saved, trading stops anyway.
+        let _ = self.transition(positions, tx, Position::Flat);
+        self.report_filled_order(tx, order);
+        // Fetched orders don't include their commission.
+        self.report_sale(
+            tx,
+            order.executed_quantity,
+            initial_price,
+            order.average_price().unwrap_or(order.price),
+            0.0,
+            ExitReason::Target,
+        );
+    }
+
+    /// Returns the commission of the fill if it was paid in the quote asset, commissions in other assets (e.g. BNB) aren't counted.
+    fn quote_commission(&self, fill: &OrderFill) -> f64 {
+        match self.assets() {
+            Ok((_, quote)) if fill.commission_asset == quote => fill.commission,
+            _ => 0.0,
+        }
+    }
+
+    /// Waits and sells the held position once the prediction has been reached.
+    /// If the prediction hasn't been reached at the end of the candle, we wait until it is reached eventually.
+    /// Unless a stop-loss, trailing stop or maximum holding time is configured, or selling is requested over telegram, we never sell at a loss!
+    /// With a take-profit order on the book, reaching the prediction only means checking whether it was filled.
+    fn monitor_position(&self, positions: &mut PositionStore, tx: &NotificationSender<TradeEvent>) {
+        let (
+            mut quantity,
+            initial_price,
+            score,
+            opened_at,
+            mut highest_price,
+            mut take_profit_order,
+        ) = match positions.position() {
+            Position::Holding {
+                quantity,
+                price,
+                target,
+                opened_at,
+                highest_price,
+                take_profit_order,
+            } => (
+                *quantity,
+                *price,
+                *target,
+                *opened_at,
+                *highest_price,
+                *take_profit_order,
+            ),
+            position => panic!("can't monitor position in state {:?}", position),
+        };
+        let exit_rules = ExitRules::from_config(&self.config.trade);
+
+        // The take-profit order may have been filled while we weren't watching, e.g. before a restart.
+        if let Some(id) = take_profit_order {
+            if let Some(order) = self.filled_take_profit_order(id) {
+                let _ = self.transition(
+                    positions,
+                    tx,
+                    Position::Selling {
+                        quantity,
+                        price: initial_price,
+                        target: score,
+                        opened_at,
+                        highest_price,
+                        take_profit_order,
+                        reason: ExitReason::Target,
+                    },
+                );
+                self.close_take_profit(positions, tx, &order, initial_price);
+                return;
+            }
+        }
+
+        // Start time of the candle a sale failed in, it's only tried again at the next candle.
+        let mut failed_sale_candle: Option<i64> = None;
+        // Selling was requested over telegram, it stays requested until the sale succeeds.
+        let mut manual_exit = false;
+        // Quantity and average price the take-profit order sold before it was canceled, if any.
+        let mut take_profit_sold: Option<(f64, f64)> = None;
 
-                match event {
-                    WebsocketEvent::Kline(kline_event) => {
-                        let initial_price = current_kline_close;
-                        let selling_price = kline_event.kline.close.parse::<f64>().unwrap();
-
-                        debug!(
-                            "Candle open: {}, close {}, high: {}, low: {}.",
-                            kline_event.kline.open,
-                            kline_event.kline.close,
-                            kline_event.kline.low,
-                            kline_event.kline.high,
-                        );
-                        debug!(
-                            "Initial price: {}, selling price: {} ({} difference).",
+        // Cleared once the position is closed, or right away when the program is terminated (e.g. CTRL + C).
+        let connected = self.scheduler.running_flag();
+        let mut web_socket = WebSockets::new(|event: WebsocketEvent| {
+            match event {
+                WebsocketEvent::Kline(kline_event) => {
+                    let selling_price = kline_event.kline.close.parse::<f64>().unwrap();
+                    let high = kline_event.kline.high.parse::<f64>().unwrap();
+                    let candles_held = self.config.interval.candles_between(
+                        Duration::from_millis(opened_at),
+                        Duration::from_millis(kline_event.kline.start_time as u64),
+                    );
+
+                    debug!(
+                        "Candle open: {}, close {}, high: {}, low: {}.",
+                        kline_event.kline.open,
+                        kline_event.kline.close,
+                        kline_event.kline.low,
+                        kline_event.kline.high,
+                    );
+                    debug!(
+                        "Initial price: {}, selling price: {} ({} difference).",
+                        initial_price,
+                        selling_price,
+                        selling_price - initial_price
+                    );
+
+                    let holding = move |highest_price: f64| Position::Holding {
+                        quantity,
+                        price: initial_price,
+                        target: score,
+                        opened_at,
+                        highest_price,
+                        take_profit_order,
+                    };
+                    let selling = move |reason: ExitReason| Position::Selling {
+                        quantity,
+                        price: initial_price,
+                        target: score,
+                        opened_at,
+                        highest_price,
+                        take_profit_order,
+                        reason,
+                    };
+
+                    // The take-profit order may have been filled by any trade during the candle, so check it as soon as the high reaches it.
+                    // The updates don't show every trade, so it's also checked whenever a candle closes.
+                    if let Some(id) = take_profit_order {
+                        if high >= score || kline_event.kline.is_final_bar {
+                            if let Some(order) = self.filled_take_profit_order(id) {
+                                let _ = self.transition(positions, tx, selling(ExitReason::Target));
+                                self.close_take_profit(positions, tx, &order, initial_price);
+                                connected.store(false, Ordering::SeqCst);
+                                return Ok(());
+                            }
+                        }
+                    }
+
+                    if failed_sale_candle == Some(kline_event.kline.start_time) {
+                        return Ok(());
+                    }
+
+                    self.receive_controls();
+                    manual_exit |= self.controls.take_sell_request();
+                    let reason = if manual_exit {
+                        Some(ExitReason::Manual)
+                    } else {
+                        exit_rules.check(
                             initial_price,
+                            score,
+                            highest_price,
                             selling_price,
-                            selling_price - initial_price
-                        );
-
-                        if selling_price >= score {
-                            let (profit, profit_percentage) = calculate_profit(
-                                self.config.trade.amount,
-                                initial_price,
-                                selling_price,
-                            );
+                            candles_held,
+                        )
+                    };
+                    let reason = match reason {
+                        // Wait for the take-profit order to be filled rather than chasing the price.
+                        Some(ExitReason::Target) if take_profit_order.is_some() => return Ok(()),
+                        Some(reason) => reason,
+                        None => {
+                            // Only the trailing stop needs the highest price, so don't bother saving it otherwise.
+                            if selling_price > highest_price && exit_rules.trailing_stop.is_some() {
+                                highest_price = selling_price;
+                                let _ = self.transition(positions, tx, holding(highest_price));
+                            }
+                            return Ok(());
+                        }
+                    };
 
-                            info!(
-                                "Selling {} {} for an estimated profit of {} USD ({}%).",
-                                self.config.trade.amount,
-                                self.config.symbol.clone(),
-                                profit,
-                                profit_percentage,
-                            );
-                            self.market
-                                .place_sell_order(
-                                    &self.config.symbol,
-                                    self.config.trade.amount,
-                                    self.config.trade.test,
+                    info!(
+                        "Selling {} {} ({}).",
+                        quantity,
+                        self.config.symbol.clone(),
+                        reason
+                    );
+                    // Never sell without knowing it was recorded, it couldn't be resumed after a crash.
+                    if self.transition(positions, tx, selling(reason)).is_err() {
+                        return Ok(());
+                    }
+
+                    // The quantity is reserved for the take-profit order, so it has to be canceled first.
+                    if let Some(id) = take_profit_order {
+                        let order = self
+                            .market
+                            .cancel_order(&self.config.symbol, id)
+                            .or_else(|_| self.market.get_order(&self.config.symbol, id));
+                        match order {
+                            Ok(order) if order.status == OrderStatus::Filled => {
+                                // It was filled in the meantime.
+                                self.close_take_profit(positions, tx, &order, initial_price);
+                                connected.store(false, Ordering::SeqCst);
+                                return Ok(());
+                            }
+                            Ok(order) => {
+                                if order.executed_quantity > 0.0 {
+                                    take_profit_sold = Some((
+                                        order.executed_quantity,
+                                        order.average_price().unwrap_or(order.price),
+                                    ));
+                                }
+                                quantity -= order.executed_quantity;
+                            }
+                            Err(err) => {
+                                // The order is still on the book, so we're still holding.
+                                if self
+                                    .transition(positions, tx, holding(highest_price))
+                                    .is_err()
+                                {
+                                    return Ok(());
+                                }
+                                self.report_error(
+                                    tx,
+                                    format!(
+                                        "Failed to cancel take-profit order {}, trying again at the next candle: {:#}",
+                                        id, err
+                                    ),
+                                );
+                                failed_sale_candle = Some(kline_event.kline.start_time);
+                                return Ok(());
+                            }
+                        }
+                        take_profit_order = None;
+                    }
+
+                    let sell_fill = match self.market.place_sell_order(
+                        &self.config.symbol,
+                        quantity,
+                        self.config.trade.test,
+                    ) {
+                        Ok(fill) => fill,
+                        Err(err) => {
+                            // The order was rejected, so we're still holding.
+                            if self
+                                .transition(
+                                    positions,
+                                    tx,
+                                    Position::Holding {
+                                        quantity,
+                                        price: initial_price,
+                                        target: score,
+                                        opened_at,
+                                        highest_price,
+                                        take_profit_order,
+                                    },
                                 )
-                                .expect("failed to place sell order");
-
-                            let msg = format!(
-                                "Sold {} {} for an estimated profit of {} USD ({}%).",
-                                self.config.trade.amount,
-                                self.config.symbol.clone(),
-                                profit,
-                                profit_percentage
+                                .is_err()
+                            {
+                                return Ok(());
+                            }
+                            self.report_error(
+                                tx,
+                                format!(
+                                    "Failed to sell {} {}, trying again at the next candle: {:#}",
+                                    quantity, self.config.symbol, err
+                                ),
                             );
-                            info!("{}", &msg);
-                            tx.send(msg).unwrap();
+                            failed_sale_candle = Some(kline_event.kline.start_time);
+                            return Ok(());
+                        }
+                    };
+                    // The sale is reported even when it couldn't be saved, trading stops anyway.
+                    let _ = self.transition(positions, tx, Position::Flat);
 
-                            connected.store(false, Ordering::SeqCst);
+                    self.report_fill(tx, OrderSide::Sell, &sell_fill);
+                    let (sold, exit_price) = match take_profit_sold {
+                        Some((take_profit_quantity, take_profit_price)) => {
+                            let sold = sell_fill.quantity + take_profit_quantity;
+                            let proceeds = sell_fill.quantity * sell_fill.price
+                                + take_profit_quantity * take_profit_price;
+                            (sold, proceeds / sold)
                         }
+                        None => (sell_fill.quantity, sell_fill.price),
+                    };
+                    self.report_sale(
+                        tx,
+                        sold,
+                        initial_price,
+                        exit_price,
+                        self.quote_commission(&sell_fill),
+                        reason,
+                    );
+                    connected.store(false, Ordering::SeqCst);
+                }
+                _ => (),
+            };
+
+            Ok(())
+        });
+
+        let subscription = format!(
+            "{}@kline_{}",
+            &self.config.symbol.to_lowercase(),
+            self.config.interval.to_string()
+        );
+        // Binance closes the connection after 24 hours, and it may drop at any time, so reconnect until we're done.
+        while connected.load(Ordering::SeqCst) {
+            if let Err(err) = web_socket.connect(&subscription) {
+                self.report_error(
+                    tx,
+                    format!(
+                        "Failed to connect to the websocket, trying again in {:?}: {}",
+                        RECONNECT_DELAY, err
+                    ),
+                );
+                self.scheduler.sleep(RECONNECT_DELAY);
+                continue;
+            }
+            if let Err(err) = web_socket.event_loop(&connected) {
+                warn!("Websocket disconnected, reconnecting: {}", err);
+            }
+            // The connection may already be gone.
+            let _ = web_socket.disconnect();
+        }
+    }
+
+    /// Logs and sends the result of a sold position.
+    /// The profit is what the sold quantity yielded, minus the commission in the quote asset, over what it cost.
+    fn report_sale(
+        &self,
+        tx: &NotificationSender<TradeEvent>,
+        quantity: f64,
+        initial_price: f64,
+        price: f64,
+        commission: f64,
+        reason: ExitReason,
+    ) {
+        let cost = quantity * initial_price;
+        let (profit, profit_percentage) = if cost > 0.0 {
+            let (profit, _) = calculate_profit(cost, initial_price, price);
+            let profit = profit - commission;
+            (profit, profit / cost * 100.0)
+        } else {
+            (0.0, 0.0)
+        };
+
+        let event = TradeEvent::PositionClosed {
+            symbol: self.config.symbol.clone(),
+            quantity,
+            entry_price: initial_price,
+            exit_price: price,
+            reason,
+            profit,
+            profit_percentage,
+        };
+        info!("{}", event);
+        self.notify(tx, event);
+        self.report_holdings(tx);
+    }
+}
+
+impl Strategy for LightGBMStrategy<BinanceMarket> {
+    fn execute(&self, tx: &NotificationSender<TradeEvent>) {
+        let mut positions = PositionStore::open(&self.config.trade.state_file, &self.config.symbol)
+            .expect("failed to load position state");
+        self.status.set_position(positions.position().clone());
+
+        if let Position::Holding {
+            quantity,
+            price,
+            target,
+            take_profit_order,
+            ..
+        } = positions.position()
+        {
+            let event = TradeEvent::PositionResumed {
+                symbol: self.config.symbol.clone(),
+                quantity: *quantity,
+                price: *price,
+                target: *target,
+                take_profit_order: *take_profit_order,
+            };
+            info!("{}", event);
+            self.notify(tx, event);
+        }
+        self.report_holdings(tx);
+
+        while !self.scheduler.is_stopped() {
+            self.receive_controls();
+            match positions.position() {
+                Position::Flat => {
+                    // There's nothing to sell anymore.
+                    self.controls.take_sell_request();
+
+                    if self.controls.is_paused() {
+                        info!("Trading is paused, waiting for the next candle.");
+                        self.scheduler.wait_for_next_candle();
+                        continue;
+                    }
+                    if !self.open_position(&mut positions, tx) {
+                        continue;
                     }
-                    _ => (),
-                };
-
-                Ok(())
-            });
-            web_socket
-                .connect(&format!("{}@kline_1h", &self.config.symbol.to_lowercase()))
-                .expect("websocket failed to connect");
-            web_socket.event_loop(&connected).unwrap();
-            web_socket.disconnect().unwrap();
+                }
+                Position::Holding { .. } => (),
+                // We crashed while an order was being placed, so we can't tell whether it was filled.
+                position => {
+                    self.report_error(
+                        tx,
+                        format!(
+                            "Interrupted while placing an order ({:?}). Check your binance account and remove {} before restarting.",
+                            position, self.config.trade.state_file
+                        ),
+                    );
+                    break;
+                }
+            }
+
+            self.monitor_position(&mut positions, tx);
         }
+
+        info!("Stopped trading.");
+        self.notify(tx, TradeEvent::Shutdown);
     }
 }
@@ -0,0 +1,112 @@
+use clap::{Parser, Subcommand};
+
+use crate::config::DEFAULT_CONFIG;
+
+/// Crypto trading bot that buys when a LightGBM model predicts the price to go up.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Config file to load.
+    #[arg(short, long, global = true, default_value = DEFAULT_CONFIG)]
+    pub config: String,
+
+    /// Starts trading when omitted.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Trade live and start the telegram bot.
+    Run,
+
+    /// Download the klines of a symbol from binance that aren't stored yet.
+    Download {
+        /// Defaults to the symbol in the config file.
+        #[arg(long)]
+        symbol: Option<String>,
+        /// Kline interval, e.g. `1h` or `1d`. Defaults to the interval in the config file.
+        #[arg(long)]
+        interval: Option<String>,
+        /// Also export all stored klines to this CSV file.
+        #[arg(long)]
+        out: Option<String>,
+    },
+
+    /// Train a model and save it to the model directory.
+    Train {
+        /// CSV file with klines, defaults to the stored klines of the configured symbol and interval.
+        #[arg(long)]
+        data: Option<String>,
+    },
+
+    /// Predict the label of the candle after the last one.
+    Predict {
+        /// CSV file with klines, defaults to the stored klines of the configured symbol and interval.
+        #[arg(long)]
+        data: Option<String>,
+        /// Saved model version, defaults to the latest one.
+        #[arg(long)]
+        model: Option<String>,
+    },
+
+    /// Replay the klines through the strategy.
+    Backtest {
+        /// CSV file with klines, defaults to the stored klines of the configured symbol and interval.
+        #[arg(long)]
+        data: Option<String>,
+    },
+
+    /// Measure how well a saved model predicts the klines.
+    Evaluate {
+        /// CSV file with klines, defaults to the stored klines of the configured symbol and interval.
+        #[arg(long)]
+        data: Option<String>,
+        /// Saved model version, defaults to the latest one.
+        #[arg(long)]
+        model: Option<String>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::{Cli, Command};
+
+    #[test]
+    fn test_parse() {
+        let cli = Cli::try_parse_from(["bot"]).unwrap();
+        assert_eq!(cli.config, "config.yaml");
+        assert!(cli.command.is_none());
+
+        let cli = Cli::try_parse_from([
+            "bot",
+            "download",
+            "--symbol",
+            "ETHUSDT",
+            "--out",
+            "klines.csv",
+            "--config",
+            "other.yaml",
+        ])
+        .unwrap();
+        assert_eq!(cli.config, "other.yaml");
+        match cli.command {
+            Some(Command::Download {
+                symbol,
+                interval,
+                out,
+            }) => {
+                assert_eq!(symbol.as_deref(), Some("ETHUSDT"));
+                assert_eq!(interval, None);
+                assert_eq!(out.as_deref(), Some("klines.csv"));
+            }
+            command => panic!("unexpected command {:?}", command),
+        }
+
+        let cli = Cli::try_parse_from(["bot", "train"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Train { data: None })));
+        assert!(Cli::try_parse_from(["bot", "train", "--data"]).is_err());
+    }
+}
@@ -1,7 +1,14 @@
-use anyhow::Ok;
+use anyhow::{anyhow, Ok};
 use config::{Config, File};
 use serde::Deserialize;
 
+use crate::{
+    binance_market::BinanceKlineInterval,
+    dataset::{FeaturePipeline, Label},
+    model::{validate_params, ModelParams},
+    retry::RetryPolicy,
+};
+
 pub const DEFAULT_CONFIG: &str = "config.yaml";
 
 #[derive(Debug, Deserialize)]
@@ -9,8 +16,24 @@ pub const DEFAULT_CONFIG: &str = "config.yaml";
 pub struct AppConfig {
     pub binance: BinanceConfig,
     pub trade: TradeConfig,
-    pub telegram: TelegramConfig,
+    /// Answers commands and sends notifications, if configured.
+    pub telegram: Option<TelegramConfig>,
+    /// Where else notifications are sent.
+    #[serde(default)]
+    pub notifiers: Vec<NotifierConfig>,
     pub symbol: String,
+    /// Kline interval the strategy trades on.
+    #[serde(default)]
+    pub interval: BinanceKlineInterval,
+    #[serde(default)]
+    pub dataset: DataSetConfig,
+    #[serde(default)]
+    pub backtest: BacktestConfig,
+    /// LightGBM parameters merged over the defaults of `Model`.
+    #[serde(default)]
+    pub model: ModelParams,
+    #[serde(default)]
+    pub training: TrainingConfig,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -18,6 +41,17 @@ pub struct AppConfig {
 pub struct BinanceConfig {
     pub api_key: String,
     pub api_secret: String,
+    /// How failed requests (other than orders) are retried.
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Maximum request weight to use per minute.
+    /// Binance allows 6000, the rest is left for other clients using the same IP.
+    #[serde(default = "default_weight_limit")]
+    pub weight_limit: u32,
+}
+
+fn default_weight_limit() -> u32 {
+    5000
 }
 
 #[derive(Debug, Deserialize)]
@@ -25,13 +59,193 @@ pub struct BinanceConfig {
 pub struct TradeConfig {
     pub amount: f64,
     pub test: bool,
+    /// File the current position is persisted to, so it survives restarts.
+    #[serde(default = "default_state_file")]
+    pub state_file: String,
+    /// Sell when the price drops this many percent below the buying price.
+    pub stop_loss: Option<f64>,
+    /// Sell when the price drops this many percent below the highest price since buying.
+    pub trailing_stop: Option<f64>,
+    /// Sell once a position has been held for this many candles.
+    pub max_holding_candles: Option<u64>,
+    /// Place a limit sell order at the predicted price right after buying,
+    /// instead of selling with a market order once the price is reached.
+    /// Only used when not in test mode, as test orders never rest on the book.
+    #[serde(default)]
+    pub take_profit_order: bool,
+    #[serde(default)]
+    pub mode: TradeMode,
+    /// Seconds to wait for a trade to be approved in `approve` mode, before skipping it.
+    #[serde(default = "default_approval_timeout")]
+    pub approval_timeout: u64,
+}
+
+fn default_state_file() -> String {
+    String::from("position.json")
+}
+
+fn default_approval_timeout() -> u64 {
+    300
+}
+
+/// Whether trades are placed right away.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TradeMode {
+    #[default]
+    Auto,
+    /// Every buy has to be approved over telegram first.
+    Approve,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 #[allow(unused)]
 pub struct TelegramConfig {
     pub bot_token: String,
-    pub chat_id: u64,
+    /// Chat the notifications are sent to, which is always allowed to use the bot.
+    /// Group chats have negative IDs.
+    pub chat_id: i64,
+    /// Other chats that may use the bot.
+    #[serde(default)]
+    pub allowed_chats: Vec<i64>,
+    /// Users that may use the bot in the allowed chats, anyone in them when empty.
+    #[serde(default)]
+    pub allowed_users: Vec<u64>,
+    /// Log the messages of everyone else, which are ignored.
+    #[serde(default)]
+    pub audit_log: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[allow(unused)]
+pub struct NotifierConfig {
+    #[serde(flatten)]
+    pub kind: NotifierKind,
+    /// Also send the events that happen at every candle, like predictions and skipped trades.
+    #[serde(default)]
+    pub routine: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotifierKind {
+    /// Posts the messages to a discord channel webhook.
+    Discord { webhook_url: String },
+    /// Posts the messages to a slack incoming webhook.
+    Slack { webhook_url: String },
+    /// Posts the events as JSON to any URL.
+    Webhook { url: String },
+    /// Mails the messages.
+    Smtp(SmtpConfig),
+    /// Prints the messages.
+    Stdout,
+    /// Appends the events as JSON lines to a file.
+    File { path: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[allow(unused)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// Defaults to the usual port of the TLS mode.
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+/// How the connection to the mail server is encrypted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpTls {
+    /// Upgrade the connection to TLS before logging in, usually on port 587.
+    #[default]
+    Starttls,
+    /// Connect over TLS right away, usually on port 465.
+    Tls,
+    /// Don't encrypt at all, only for local mail servers.
+    None,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[allow(unused)]
+pub struct DataSetConfig {
+    /// Directory the klines of every symbol and interval are stored in.
+    #[serde(default = "default_kline_dir")]
+    pub kline_dir: String,
+    /// What the model is trained to predict.
+    #[serde(default)]
+    pub label: Label,
+    /// The features the model is trained on, by name.
+    #[serde(default)]
+    pub features: FeaturePipeline,
+}
+
+fn default_kline_dir() -> String {
+    String::from("klines")
+}
+
+impl Default for DataSetConfig {
+    fn default() -> Self {
+        Self {
+            kline_dir: default_kline_dir(),
+            label: Label::default(),
+            features: FeaturePipeline::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[allow(unused)]
+pub struct BacktestConfig {
+    /// Quote asset balance to start the backtest with.
+    pub balance: f64,
+    /// Commission charged on every simulated fill, as a fraction (e.g. `0.001` for 0.1%).
+    pub fee: f64,
+    /// Amount of candles used to train the first model before any trade is simulated.
+    pub warmup: usize,
+    /// Retrain the model every N candles while no position is open.
+    /// The live bot retrains before every trade, which corresponds to `1`.
+    pub retrain_interval: usize,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        Self {
+            balance: 1000.0,
+            fee: 0.001,
+            warmup: 500,
+            retrain_interval: 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+#[allow(unused)]
+pub struct TrainingConfig {
+    /// Directory trained models and their metadata are saved to.
+    pub model_dir: String,
+    /// Always use this saved model (e.g. `btcusdt-1674925200000`) instead of the latest one, and never retrain.
+    pub model: Option<String>,
+    /// Retrain once this many new candles have closed since the current model was trained.
+    pub retrain_interval: u64,
+    /// Only train on the last N candles instead of the full history.
+    pub window: Option<u64>,
+}
+
+impl Default for TrainingConfig {
+    fn default() -> Self {
+        Self {
+            model_dir: String::from("models"),
+            model: None,
+            retrain_interval: 1,
+            window: None,
+        }
+    }
 }
 
 /// Load the specified config file.
@@ -42,6 +256,33 @@ pub fn load_config(name: &str) -> anyhow::Result<AppConfig> {
 
     let config = settings.try_deserialize::<AppConfig>()?;
 
+    if config.dataset.label.horizon() == 0 {
+        return Err(anyhow!("dataset.label.horizon must be at least 1"));
+    }
+
+    validate_params(&config.model)?;
+
+    if config.trade.mode == TradeMode::Approve && config.telegram.is_none() {
+        return Err(anyhow!(
+            "trade.mode approve needs telegram to be configured, as trades are approved there"
+        ));
+    }
+
+    if config.training.retrain_interval == 0 {
+        return Err(anyhow!("training.retrain_interval must be at least 1"));
+    }
+
+    // Otherwise not a single candle of the window has both its features and its label.
+    let warmup = config.dataset.features.warmup();
+    let horizon = config.dataset.label.horizon();
+    if matches!(config.training.window, Some(window) if window as usize <= warmup + horizon) {
+        return Err(anyhow!(
+            "training.window must be longer than the feature warmup ({}) plus the label horizon ({})",
+            warmup,
+            horizon
+        ));
+    }
+
     Ok(config)
 }
 
@@ -53,9 +294,15 @@ pub fn try_load_config(name: &str) -> AppConfig {
 
 #[cfg(test)]
 mod tests {
-    use crate::config::try_load_config;
+    use crate::{
+        binance_market::BinanceKlineInterval, config::try_load_config, dataset::Label,
+        retry::RetryPolicy,
+    };
+
+    use config::{Config, File, FileFormat};
+    use serde::Deserialize;
 
-    use super::load_config;
+    use super::{load_config, NotifierConfig, NotifierKind, SmtpConfig, SmtpTls, TradeMode};
 
     #[test]
     fn test_load_config() {
@@ -70,13 +317,89 @@ mod tests {
     fn test_try_load_config() {
         let config = try_load_config("config.example.yaml");
         assert_eq!(config.symbol, "BTCUSDT");
+        assert_eq!(config.interval, BinanceKlineInterval::Hourly);
         assert_eq!(config.binance.api_key, "paste your binance api key here");
         assert_eq!(
             config.binance.api_secret,
             "paste your binance api secret here"
         );
+        assert_eq!(config.binance.retry, RetryPolicy::default());
+        assert_eq!(config.binance.weight_limit, 5000);
         assert_eq!(config.trade.test, true);
         assert_eq!(config.trade.amount, 50.0);
-        assert_eq!(config.telegram.bot_token, "123456789:blablabla");
+        assert_eq!(config.trade.state_file, "position.json");
+        assert_eq!(config.trade.stop_loss, None);
+        assert_eq!(config.trade.trailing_stop, None);
+        assert_eq!(config.trade.max_holding_candles, None);
+        assert_eq!(config.trade.take_profit_order, false);
+        assert_eq!(config.trade.mode, TradeMode::Auto);
+        assert_eq!(config.trade.approval_timeout, 300);
+        let telegram = config.telegram.as_ref().unwrap();
+        assert_eq!(telegram.bot_token, "123456789:blablabla");
+        assert_eq!(telegram.allowed_chats, Vec::<i64>::new());
+        assert_eq!(telegram.allowed_users, Vec::<u64>::new());
+        assert_eq!(telegram.audit_log, false);
+        assert_eq!(config.notifiers.len(), 0);
+        assert_eq!(config.dataset.kline_dir, "klines");
+        assert_eq!(config.dataset.label, Label::MaxHigh { horizon: 1 });
+        assert_eq!(config.dataset.features.names(), vec!["open"]);
+        assert_eq!(config.backtest.balance, 1000.0);
+        assert_eq!(config.backtest.fee, 0.001);
+        assert_eq!(config.backtest.warmup, 500);
+        assert_eq!(config.backtest.retrain_interval, 1);
+        assert_eq!(config.model["num_leaves"], 13);
+        assert_eq!(config.model["num_iterations"], 1000);
+        assert_eq!(config.training.model_dir, "models");
+        assert_eq!(config.training.model, None);
+        assert_eq!(config.training.retrain_interval, 24);
+        assert_eq!(config.training.window, None);
+    }
+
+    #[test]
+    fn test_notifiers() {
+        #[derive(Deserialize)]
+        struct Notifiers {
+            notifiers: Vec<NotifierConfig>,
+        }
+
+        let yaml = r#"
+notifiers:
+  - type: discord
+    webhook_url: https://discord.com/api/webhooks/1/abc
+  - type: smtp
+    host: smtp.example.com
+    from: bot@example.com
+    to: [me@example.com]
+  - type: stdout
+    routine: true
+"#;
+        let config = Config::builder()
+            .add_source(File::from_str(yaml, FileFormat::Yaml))
+            .build()
+            .unwrap()
+            .try_deserialize::<Notifiers>()
+            .unwrap();
+
+        assert_eq!(
+            config.notifiers[0].kind,
+            NotifierKind::Discord {
+                webhook_url: String::from("https://discord.com/api/webhooks/1/abc")
+            }
+        );
+        assert!(!config.notifiers[0].routine);
+        assert_eq!(
+            config.notifiers[1].kind,
+            NotifierKind::Smtp(SmtpConfig {
+                host: String::from("smtp.example.com"),
+                port: None,
+                tls: SmtpTls::Starttls,
+                username: None,
+                password: None,
+                from: String::from("bot@example.com"),
+                to: vec![String::from("me@example.com")],
+            })
+        );
+        assert_eq!(config.notifiers[2].kind, NotifierKind::Stdout);
+        assert!(config.notifiers[2].routine);
     }
 }
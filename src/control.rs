@@ -0,0 +1,118 @@
+use std::cell::{Cell, RefCell};
+
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// Command to change what the strategy does, sent over telegram.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Control {
+    /// Stop opening new positions, an open position is still monitored.
+    Pause,
+    /// Open new positions again.
+    Resume,
+    /// Sell the open position at market price.
+    SellNow,
+    /// Answer to a request to approve a trade.
+    Decide(Decision),
+}
+
+/// Whether a trade was approved, and by whom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    /// ID of the approval request.
+    pub request: u64,
+    pub approved: bool,
+    pub by: String,
+}
+
+/// Receiving end of the control channel, which the strategy checks whenever it's about to act.
+pub struct Controls {
+    rx: RefCell<UnboundedReceiver<Control>>,
+    paused: Cell<bool>,
+    sell_requested: Cell<bool>,
+    decision: RefCell<Option<Decision>>,
+}
+
+/// Creates a control channel, sending never blocks.
+pub fn channel() -> (UnboundedSender<Control>, Controls) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    (
+        tx,
+        Controls {
+            rx: RefCell::new(rx),
+            paused: Cell::new(false),
+            sell_requested: Cell::new(false),
+            decision: RefCell::new(None),
+        },
+    )
+}
+
+impl Controls {
+    /// Applies the commands sent since the last call.
+    pub fn receive(&self) {
+        let mut rx = self.rx.borrow_mut();
+        while let Ok(control) = rx.try_recv() {
+            info!("Received control command {:?}.", control);
+            match control {
+                Control::Pause => self.paused.set(true),
+                Control::Resume => self.paused.set(false),
+                Control::SellNow => self.sell_requested.set(true),
+                Control::Decide(decision) => *self.decision.borrow_mut() = Some(decision),
+            }
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.get()
+    }
+
+    /// Returns whether selling was requested, only once per request.
+    pub fn take_sell_request(&self) -> bool {
+        self.sell_requested.replace(false)
+    }
+
+    /// Returns the decision on the approval request, answers to earlier requests are dropped.
+    pub fn take_decision(&self, request: u64) -> Option<Decision> {
+        self.decision
+            .borrow_mut()
+            .take()
+            .filter(|decision| decision.request == request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{channel, Control, Decision};
+
+    #[test]
+    fn test_controls() {
+        let (tx, controls) = channel();
+        tx.send(Control::Pause).unwrap();
+        tx.send(Control::SellNow).unwrap();
+
+        // Nothing changes until the commands are received.
+        assert!(!controls.is_paused());
+        controls.receive();
+        assert!(controls.is_paused());
+        assert!(controls.take_sell_request());
+        assert!(!controls.take_sell_request());
+
+        let decision = |request| {
+            Control::Decide(Decision {
+                request,
+                approved: true,
+                by: String::from("alice"),
+            })
+        };
+        tx.send(decision(1)).unwrap();
+        tx.send(Control::Resume).unwrap();
+        controls.receive();
+        assert!(!controls.is_paused());
+        assert_eq!(controls.take_decision(1).map(|d| d.approved), Some(true));
+
+        // An answer to an earlier request is dropped.
+        tx.send(decision(1)).unwrap();
+        controls.receive();
+        assert_eq!(controls.take_decision(2), None);
+        assert_eq!(controls.take_decision(1), None);
+    }
+}
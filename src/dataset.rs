@@ -1,6 +1,11 @@
-use csv::ReaderBuilder;
+use std::{fmt, str::FromStr};
 
-use crate::binance_market::{BinanceKlineOptions, BinanceMarket};
+use anyhow::anyhow;
+use binance::model::KlineSummary;
+use csv::{ReaderBuilder, StringRecord};
+use serde::{Deserialize, Serialize};
+
+use crate::indicators;
 
 pub type Features = Vec<Vec<f64>>;
 pub type Labels = Vec<f32>;
@@ -8,45 +13,452 @@ pub type Labels = Vec<f32>;
 #[derive(Debug, Clone)]
 pub struct DataSet(pub Features, pub Labels);
 
-impl From<&str> for DataSet {
-    /// Extracts the necessary `labels` and `features` from the given dataset.
-    /// Only supports CSV files.
-    fn from(csv_file_path: &str) -> Self {
-        let reader = ReaderBuilder::new()
+/// A single kline (candle).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candle {
+    pub open_time: u64,
+    pub close_time: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+}
+
+impl Candle {
+    /// Reads all candles from a CSV file written by `KlineStore`.
+    pub fn from_csv(csv_file_path: &str) -> anyhow::Result<Vec<Candle>> {
+        let mut reader = ReaderBuilder::new()
             .has_headers(true)
             .delimiter(b',')
-            .from_path(csv_file_path);
+            .from_path(csv_file_path)?;
+
+        let mut candles = Vec::new();
 
-        let mut labels: Labels = Vec::new();
-        let mut features: Features = Vec::new();
+        for result in reader.records() {
+            candles.push(Candle::from_record(&result?)?);
+        }
 
-        for result in reader.unwrap().records() {
-            let record = result.unwrap();
+        Ok(candles)
+    }
 
-            let label = record[4].parse::<f32>().unwrap(); // high
-            let feature: Vec<f64> = vec![record[3].parse::<f64>().unwrap()]; // open
+    /// Parses a single row of a CSV file written by `KlineStore`.
+    pub fn from_record(record: &StringRecord) -> anyhow::Result<Candle> {
+        Ok(Candle {
+            open_time: record[0].parse()?,
+            close_time: record[1].parse()?,
+            open: record[3].parse()?,
+            high: record[4].parse()?,
+            low: record[5].parse()?,
+            close: record[6].parse()?,
+            volume: record[7].parse()?,
+        })
+    }
+}
 
-            labels.push(label);
-            features.push(feature);
+impl From<KlineSummary> for Candle {
+    fn from(kline: KlineSummary) -> Self {
+        Candle {
+            open_time: kline.open_time as u64,
+            close_time: kline.close_time as u64,
+            open: kline.open.parse().unwrap(),
+            high: kline.high.parse().unwrap(),
+            low: kline.low.parse().unwrap(),
+            close: kline.close.parse().unwrap(),
+            volume: kline.volume.parse().unwrap(),
         }
+    }
+}
 
-        Self(features, labels)
+/// What the model learns to predict for every candle.
+/// The label of a candle is always taken from the candles after it, so no look-ahead slips into the features.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "target", rename_all = "snake_case")]
+pub enum Label {
+    /// Highest `high` over the next `horizon` candles.
+    MaxHigh { horizon: usize },
+    /// `close` of the candle `horizon` candles ahead.
+    Close { horizon: usize },
+}
+
+impl Default for Label {
+    fn default() -> Self {
+        Label::MaxHigh { horizon: 1 }
+    }
+}
+
+impl Label {
+    pub fn horizon(&self) -> usize {
+        match self {
+            Label::MaxHigh { horizon } | Label::Close { horizon } => *horizon,
+        }
+    }
+
+    /// Returns the label of every candle, or `None` for the last `horizon` candles which can't be labeled yet.
+    pub fn build(&self, candles: &[Candle]) -> Vec<Option<f32>> {
+        let horizon = self.horizon();
+
+        (0..candles.len())
+            .map(|i| {
+                let next = candles.get(i + 1..=i + horizon)?;
+                let label = match self {
+                    Label::MaxHigh { .. } => next
+                        .iter()
+                        .map(|candle| candle.high)
+                        .fold(f64::MIN, f64::max),
+                    Label::Close { .. } => next.last()?.close,
+                };
+                Some(label as f32)
+            })
+            .collect()
+    }
+}
+
+/// A single feature computed from kline OHLCV data.
+/// Parsed from names such as `open`, `sma_20` or `rsi_14`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    Open,
+    High,
+    Low,
+    Close,
+    Volume,
+    /// Simple moving average of the close price.
+    Sma(usize),
+    /// Exponential moving average of the close price.
+    Ema(usize),
+    /// Relative strength index.
+    Rsi(usize),
+    /// MACD line with the standard 12/26 periods.
+    Macd,
+    /// MACD signal line with the standard 12/26/9 periods.
+    MacdSignal,
+    /// Difference between the MACD and signal line.
+    MacdHistogram,
+    /// Width of the Bollinger bands (2 standard deviations) relative to the moving average.
+    BollingerWidth(usize),
+    /// Average true range.
+    Atr(usize),
+    /// Volume relative to its moving average.
+    VolumeRatio(usize),
+}
+
+impl FromStr for Feature {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let feature = match name {
+            "open" => Feature::Open,
+            "high" => Feature::High,
+            "low" => Feature::Low,
+            "close" => Feature::Close,
+            "volume" => Feature::Volume,
+            "macd" => Feature::Macd,
+            "macd_signal" => Feature::MacdSignal,
+            "macd_histogram" => Feature::MacdHistogram,
+            _ => {
+                let (indicator, period) = name
+                    .rsplit_once('_')
+                    .ok_or(anyhow!("unknown feature {}", name))?;
+                let period: usize = period
+                    .parse()
+                    .map_err(|_| anyhow!("invalid period in feature {}", name))?;
+                if period == 0 {
+                    return Err(anyhow!("invalid period in feature {}", name));
+                }
+                match indicator {
+                    "sma" => Feature::Sma(period),
+                    "ema" => Feature::Ema(period),
+                    "rsi" => Feature::Rsi(period),
+                    "bb_width" => Feature::BollingerWidth(period),
+                    "atr" => Feature::Atr(period),
+                    "volume_ratio" => Feature::VolumeRatio(period),
+                    _ => return Err(anyhow!("unknown feature {}", name)),
+                }
+            }
+        };
+
+        Ok(feature)
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Feature::Open => write!(f, "open"),
+            Feature::High => write!(f, "high"),
+            Feature::Low => write!(f, "low"),
+            Feature::Close => write!(f, "close"),
+            Feature::Volume => write!(f, "volume"),
+            Feature::Sma(period) => write!(f, "sma_{}", period),
+            Feature::Ema(period) => write!(f, "ema_{}", period),
+            Feature::Rsi(period) => write!(f, "rsi_{}", period),
+            Feature::Macd => write!(f, "macd"),
+            Feature::MacdSignal => write!(f, "macd_signal"),
+            Feature::MacdHistogram => write!(f, "macd_histogram"),
+            Feature::BollingerWidth(period) => write!(f, "bb_width_{}", period),
+            Feature::Atr(period) => write!(f, "atr_{}", period),
+            Feature::VolumeRatio(period) => write!(f, "volume_ratio_{}", period),
+        }
+    }
+}
+
+impl Feature {
+    /// Amount of candles at the start for which this feature isn't defined yet.
+    pub fn warmup(&self) -> usize {
+        match self {
+            Feature::Open | Feature::High | Feature::Low | Feature::Close | Feature::Volume => 0,
+            Feature::Sma(period)
+            | Feature::Ema(period)
+            | Feature::BollingerWidth(period)
+            | Feature::Atr(period)
+            | Feature::VolumeRatio(period) => period - 1,
+            // The first change is only known at the second candle.
+            Feature::Rsi(period) => *period,
+            // The slow EMA, then the signal EMA over the MACD line.
+            Feature::Macd => 26 - 1,
+            Feature::MacdSignal | Feature::MacdHistogram => 26 - 1 + 9 - 1,
+        }
+    }
+
+    /// Computes this feature for every candle, `None` while the indicator is warming up.
+    fn compute(&self, candles: &[Candle]) -> Vec<Option<f64>> {
+        let series = |f: fn(&Candle) -> f64| candles.iter().map(f).collect::<Vec<f64>>();
+        let closes = series(|candle| candle.close);
+
+        match self {
+            Feature::Open => series(|candle| candle.open).into_iter().map(Some).collect(),
+            Feature::High => series(|candle| candle.high).into_iter().map(Some).collect(),
+            Feature::Low => series(|candle| candle.low).into_iter().map(Some).collect(),
+            Feature::Close => closes.into_iter().map(Some).collect(),
+            Feature::Volume => series(|candle| candle.volume)
+                .into_iter()
+                .map(Some)
+                .collect(),
+            Feature::Sma(period) => indicators::sma(&closes, *period),
+            Feature::Ema(period) => indicators::ema(&closes, *period),
+            Feature::Rsi(period) => indicators::rsi(&closes, *period),
+            Feature::Macd => indicators::macd(&closes, 12, 26, 9).0,
+            Feature::MacdSignal => indicators::macd(&closes, 12, 26, 9).1,
+            Feature::MacdHistogram => indicators::macd(&closes, 12, 26, 9).2,
+            Feature::BollingerWidth(period) => indicators::bollinger_width(&closes, *period, 2.0),
+            Feature::Atr(period) => indicators::atr(
+                &series(|candle| candle.high),
+                &series(|candle| candle.low),
+                &closes,
+                *period,
+            ),
+            Feature::VolumeRatio(period) => {
+                indicators::volume_ratio(&series(|candle| candle.volume), *period)
+            }
+        }
+    }
+}
+
+/// The features the model is trained on, in order.
+/// Features of a candle only depend on that candle and the ones before it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "Vec<String>")]
+pub struct FeaturePipeline(Vec<Feature>);
+
+impl Default for FeaturePipeline {
+    fn default() -> Self {
+        Self(vec![Feature::Open])
+    }
+}
+
+impl TryFrom<Vec<String>> for FeaturePipeline {
+    type Error = anyhow::Error;
+
+    fn try_from(names: Vec<String>) -> Result<Self, Self::Error> {
+        if names.is_empty() {
+            return Err(anyhow!("at least one feature is required"));
+        }
+
+        let features = names
+            .iter()
+            .map(|name| name.parse())
+            .collect::<anyhow::Result<Vec<Feature>>>()?;
+
+        Ok(Self(features))
+    }
+}
+
+impl FeaturePipeline {
+    pub fn new(features: Vec<Feature>) -> Self {
+        Self(features)
+    }
+
+    pub fn features(&self) -> &[Feature] {
+        &self.0
+    }
+
+    /// Amount of candles at the start for which not every feature is defined yet.
+    pub fn warmup(&self) -> usize {
+        self.0.iter().map(Feature::warmup).max().unwrap_or(0)
+    }
+
+    /// Returns the feature names, e.g. for logging.
+    pub fn names(&self) -> Vec<String> {
+        self.0.iter().map(Feature::to_string).collect()
+    }
+
+    /// Computes the features of every candle, or `None` when one of them isn't defined yet.
+    pub fn compute(&self, candles: &[Candle]) -> Vec<Option<Vec<f64>>> {
+        let columns: Vec<Vec<Option<f64>>> = self
+            .0
+            .iter()
+            .map(|feature| feature.compute(candles))
+            .collect();
+
+        (0..candles.len())
+            .map(|i| columns.iter().map(|column| column[i]).collect())
+            .collect()
     }
 }
 
 impl DataSet {
-    /// Extracts the necessary `labels` and `features` from kline data from Binance.
-    pub fn from_binance(market: &BinanceMarket, options: BinanceKlineOptions) -> Self {
-        let mut labels: Labels = Vec::new();
-        let mut features: Features = Vec::new();
+    /// Extracts the `features` and `labels` from the given candles, oldest first.
+    /// Candles that can't be labeled yet or whose features are still warming up are dropped.
+    pub fn new(candles: &[Candle], label: &Label, pipeline: &FeaturePipeline) -> Self {
+        Self::from_parts(&pipeline.compute(candles), &label.build(candles))
+    }
+
+    /// Combines precomputed features and labels, dropping the rows where either one is missing.
+    pub fn from_parts(features: &[Option<Vec<f64>>], labels: &[Option<f32>]) -> Self {
+        let mut dataset = Self(Vec::new(), Vec::new());
+
+        for (feature, label) in features.iter().zip(labels) {
+            if let (Some(feature), Some(label)) = (feature, label) {
+                dataset.0.push(feature.clone());
+                dataset.1.push(*label);
+            }
+        }
+
+        dataset
+    }
+
+    /// Returns the features of the last candle, to predict what comes after it.
+    /// Computed over the same candles as the training data, so predictions match what the model was trained on.
+    pub fn latest_features(candles: &[Candle], pipeline: &FeaturePipeline) -> Option<Vec<f64>> {
+        pipeline.compute(candles).pop().flatten()
+    }
+
+    /// Extracts the necessary `labels` and `features` from the given dataset.
+    /// Only supports CSV files.
+    pub fn from_csv(
+        csv_file_path: &str,
+        label: &Label,
+        pipeline: &FeaturePipeline,
+    ) -> anyhow::Result<Self> {
+        let candles = Candle::from_csv(csv_file_path)?;
+        Ok(Self::new(&candles, label, pipeline))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Candle, DataSet, Feature, FeaturePipeline, Label};
+
+    fn candle(open: f64, high: f64, close: f64) -> Candle {
+        Candle {
+            open_time: 0,
+            close_time: 0,
+            open,
+            high,
+            low: open.min(close),
+            close,
+            volume: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_label() {
+        let candles = vec![
+            candle(1.0, 2.0, 1.5),
+            candle(1.5, 4.0, 3.0),
+            candle(3.0, 3.5, 2.0),
+            candle(2.0, 2.5, 2.5),
+        ];
+
+        let label = Label::MaxHigh { horizon: 2 };
+        assert_eq!(
+            label.build(&candles),
+            vec![Some(4.0), Some(3.5), None, None]
+        );
+
+        let label = Label::Close { horizon: 1 };
+        assert_eq!(
+            label.build(&candles),
+            vec![Some(3.0), Some(2.0), Some(2.5), None]
+        );
+    }
+
+    #[test]
+    fn test_new() {
+        let candles = vec![
+            candle(1.0, 2.0, 1.5),
+            candle(1.5, 4.0, 3.0),
+            candle(3.0, 3.5, 2.0),
+        ];
+
+        let pipeline = FeaturePipeline::default();
+        let DataSet(features, labels) =
+            DataSet::new(&candles, &Label::MaxHigh { horizon: 1 }, &pipeline);
+        assert_eq!(features, vec![vec![1.0], vec![1.5]]);
+        assert_eq!(labels, vec![4.0, 3.5]);
+
+        // The first row has no moving average yet and the last one no label.
+        let pipeline = FeaturePipeline::new(vec![Feature::Close, Feature::Sma(2)]);
+        let DataSet(features, labels) =
+            DataSet::new(&candles, &Label::MaxHigh { horizon: 1 }, &pipeline);
+        assert_eq!(features, vec![vec![3.0, 2.25]]);
+        assert_eq!(labels, vec![3.5]);
+        assert_eq!(
+            DataSet::latest_features(&candles, &pipeline),
+            Some(vec![2.0, 2.5])
+        );
+    }
+
+    #[test]
+    fn test_feature_names() {
+        let names = [
+            "open",
+            "volume",
+            "sma_20",
+            "ema_12",
+            "rsi_14",
+            "macd",
+            "macd_signal",
+            "macd_histogram",
+            "bb_width_20",
+            "atr_14",
+            "volume_ratio_20",
+        ];
+        let pipeline = FeaturePipeline::try_from(
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+        assert_eq!(pipeline.features()[2], Feature::Sma(20));
+        assert_eq!(pipeline.features()[8], Feature::BollingerWidth(20));
+        assert_eq!(pipeline.names(), names);
 
-        for kline in market.get_klines(options) {
-            let label = kline.high.parse::<f32>().unwrap();
-            let feature: Vec<f64> = vec![kline.open.parse::<f64>().unwrap()];
-            labels.push(label);
-            features.push(feature);
+        // The warmup matches the candles the indicators actually leave undefined.
+        let candles: Vec<Candle> = (1..=60)
+            .map(|i| candle(i as f64, i as f64 + 1.0, i as f64 + 0.5))
+            .collect();
+        for feature in pipeline.features() {
+            let defined = feature.compute(&candles).iter().position(Option::is_some);
+            assert_eq!(defined, Some(feature.warmup()), "{}", feature);
         }
+        assert_eq!(pipeline.warmup(), 33);
 
-        Self(features, labels)
+        assert!("sma".parse::<Feature>().is_err());
+        assert!("sma_0".parse::<Feature>().is_err());
+        assert!("foo_10".parse::<Feature>().is_err());
+        assert!(FeaturePipeline::try_from(Vec::new()).is_err());
     }
 }
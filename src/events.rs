@@ -0,0 +1,385 @@
+use std::{fmt, time::Duration};
+
+use serde::Serialize;
+
+use crate::{
+    dataset::Label,
+    market::{Balance, OrderSide, OrderType},
+    position::ExitReason,
+    utils::format_duration,
+};
+
+/// Everything noteworthy the strategy does while trading.
+/// Notifiers, loggers and journals each render or store these their own way, `Display` gives a message for humans.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TradeEvent {
+    /// A new model was trained and saved.
+    ModelTrained {
+        symbol: String,
+        version: String,
+        samples: usize,
+        rmse: f64,
+        mae: f64,
+    },
+
+    /// The model predicted the label of the next candle.
+    Prediction {
+        symbol: String,
+        model: String,
+        label: Label,
+        predicted: f64,
+        /// Open and current price of the candle that is forming.
+        open: f64,
+        close: f64,
+    },
+
+    /// No position was opened at this candle.
+    TradeSkipped { symbol: String, reason: SkipReason },
+
+    /// In `approve` mode, a buy waits for approval.
+    ApprovalRequested {
+        /// ID the decision refers to.
+        request: u64,
+        symbol: String,
+        /// Quote asset amount to spend.
+        amount: f64,
+        predicted: f64,
+        open: f64,
+        close: f64,
+        /// Profit if the predicted price is reached, when buying at the current price.
+        expected_profit: f64,
+        expected_profit_percentage: f64,
+        /// Seconds to wait for the decision.
+        timeout: u64,
+    },
+
+    /// A buy was approved, rejected buys are skipped.
+    TradeApproved {
+        request: u64,
+        symbol: String,
+        by: String,
+    },
+
+    /// An order was placed on the book, market orders are only reported once filled.
+    OrderPlaced {
+        symbol: String,
+        order_id: u64,
+        side: OrderSide,
+        order_type: OrderType,
+        quantity: f64,
+        price: f64,
+    },
+
+    /// An order was filled, 0 being the ID of test orders.
+    OrderFilled {
+        symbol: String,
+        order_id: u64,
+        side: OrderSide,
+        quantity: f64,
+        /// Average price of all fills.
+        price: f64,
+        /// Unknown for orders that rested on the book.
+        commission: Option<f64>,
+        commission_asset: Option<String>,
+    },
+
+    /// A position held before the bot was restarted is monitored again.
+    PositionResumed {
+        symbol: String,
+        quantity: f64,
+        price: f64,
+        target: f64,
+        take_profit_order: Option<u64>,
+    },
+
+    /// A position was sold.
+    PositionClosed {
+        symbol: String,
+        quantity: f64,
+        entry_price: f64,
+        exit_price: f64,
+        reason: ExitReason,
+        /// Profit in the quote asset, negative for a loss.
+        profit: f64,
+        profit_percentage: f64,
+    },
+
+    /// Total balances of the traded assets.
+    Holdings { base: Balance, quote: Balance },
+
+    /// Something went wrong, the strategy either tries again later or stops.
+    Error { message: String },
+
+    /// The strategy stopped trading.
+    Shutdown,
+}
+
+/// Why no position was opened.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The predicted price is below the open or current price of the candle.
+    PredictionBelowPrice {
+        predicted: f64,
+        open: f64,
+        close: f64,
+    },
+    /// The free quote asset balance doesn't cover the trade amount.
+    InsufficientFunds {
+        asset: String,
+        required: f64,
+        available: f64,
+    },
+    /// In `approve` mode, the trade was rejected.
+    Rejected { by: String },
+    /// In `approve` mode, there was no decision in time.
+    ApprovalExpired,
+    /// In `approve` mode, the price rose above the prediction while waiting for the approval.
+    PriceAboveTarget { predicted: f64, price: f64 },
+}
+
+impl TradeEvent {
+    /// Whether the event happens at (nearly) every candle, notifiers skip these to not flood their channel.
+    pub fn is_routine(&self) -> bool {
+        matches!(
+            self,
+            TradeEvent::Prediction { .. }
+                | TradeEvent::TradeSkipped {
+                    reason: SkipReason::PredictionBelowPrice { .. },
+                    ..
+                }
+        )
+    }
+}
+
+impl fmt::Display for TradeEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TradeEvent::ModelTrained {
+                symbol,
+                version,
+                samples,
+                rmse,
+                mae,
+            } => write!(
+                f,
+                "Trained model {} for {} on {} samples (rmse: {}, mae: {}).",
+                version, symbol, samples, rmse, mae
+            ),
+            TradeEvent::Prediction {
+                symbol,
+                model,
+                label,
+                predicted,
+                open,
+                close,
+            } => write!(
+                f,
+                "Model {} predicts {:?} of {}: {} (open: {}, current: {}).",
+                model, label, symbol, predicted, open, close
+            ),
+            TradeEvent::TradeSkipped { symbol, reason } => {
+                write!(f, "Skipping {} trade: {}.", symbol, reason)
+            }
+            TradeEvent::ApprovalRequested {
+                symbol,
+                amount,
+                predicted,
+                open,
+                close,
+                expected_profit,
+                expected_profit_percentage,
+                timeout,
+                ..
+            } => write!(
+                f,
+                "Buy {} for {}? The model predicts {} (open: {}, current: {}), for a profit of {} USD ({}%). The trade is skipped unless it's approved within {}.",
+                symbol,
+                amount,
+                predicted,
+                open,
+                close,
+                expected_profit,
+                expected_profit_percentage,
+                format_duration(Duration::from_secs(*timeout))
+            ),
+            TradeEvent::TradeApproved {
+                request,
+                symbol,
+                by,
+            } => write!(f, "{} trade {} was approved by {}.", symbol, request, by),
+            TradeEvent::OrderPlaced {
+                symbol,
+                order_id,
+                side,
+                order_type,
+                quantity,
+                price,
+            } => write!(
+                f,
+                "Placed {} {} order {} for {} {} at {}.",
+                order_type, side, order_id, quantity, symbol, price
+            ),
+            TradeEvent::OrderFilled {
+                symbol,
+                side,
+                quantity,
+                price,
+                commission,
+                commission_asset,
+                ..
+            } => {
+                let verb = match side {
+                    OrderSide::Buy => "Bought",
+                    OrderSide::Sell => "Sold",
+                };
+                write!(f, "{} {} {} at {}", verb, quantity, symbol, price)?;
+                if let (Some(commission), Some(asset)) = (commission, commission_asset) {
+                    write!(f, " (commission: {} {})", commission, asset)?;
+                }
+                f.write_str(".")
+            }
+            TradeEvent::PositionResumed {
+                symbol,
+                quantity,
+                price,
+                target,
+                take_profit_order,
+            } => match take_profit_order {
+                Some(id) => write!(
+                    f,
+                    "Resuming position of {} {} bought at {}, take-profit order {} at {}.",
+                    quantity, symbol, price, id, target
+                ),
+                None => write!(
+                    f,
+                    "Resuming position of {} {} bought at {}, selling at {}.",
+                    quantity, symbol, price, target
+                ),
+            },
+            TradeEvent::PositionClosed {
+                symbol,
+                quantity,
+                exit_price,
+                reason,
+                profit,
+                profit_percentage,
+                ..
+            } => write!(
+                f,
+                "Closed position of {} {} at {} ({}) for a profit of {} USD ({}%).",
+                quantity, symbol, exit_price, reason, profit, profit_percentage
+            ),
+            TradeEvent::Holdings { base, quote } => write!(
+                f,
+                "Holding {} {} and {} {}.",
+                base.total(),
+                base.asset,
+                quote.total(),
+                quote.asset
+            ),
+            TradeEvent::Error { message } => f.write_str(message),
+            TradeEvent::Shutdown => f.write_str("Trading stopped."),
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::PredictionBelowPrice {
+                predicted,
+                open,
+                close,
+            } => write!(
+                f,
+                "predicted value {} is lower than the open ({}) or current ({}) price",
+                predicted, open, close
+            ),
+            SkipReason::InsufficientFunds {
+                asset,
+                required,
+                available,
+            } => write!(
+                f,
+                "insufficient funds, need {} {} but only {} is available",
+                required, asset, available
+            ),
+            SkipReason::Rejected { by } => write!(f, "rejected by {}", by),
+            SkipReason::ApprovalExpired => f.write_str("it wasn't approved in time"),
+            SkipReason::PriceAboveTarget { predicted, price } => write!(
+                f,
+                "the price rose to {} while waiting for the approval, above the predicted value {}",
+                price, predicted
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{market::OrderSide, position::ExitReason};
+
+    use super::{SkipReason, TradeEvent};
+
+    #[test]
+    fn test_trade_event() {
+        let event = TradeEvent::PositionClosed {
+            symbol: String::from("BTCUSDT"),
+            quantity: 0.002,
+            entry_price: 20000.0,
+            exit_price: 21000.0,
+            reason: ExitReason::Target,
+            profit: 2.5,
+            profit_percentage: 5.0,
+        };
+        assert_eq!(
+            event.to_string(),
+            "Closed position of 0.002 BTCUSDT at 21000 (target reached) for a profit of 2.5 USD (5%)."
+        );
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"position_closed","symbol":"BTCUSDT","quantity":0.002,"entry_price":20000.0,"exit_price":21000.0,"reason":"target","profit":2.5,"profit_percentage":5.0}"#
+        );
+        assert!(!event.is_routine());
+
+        let event = TradeEvent::OrderFilled {
+            symbol: String::from("BTCUSDT"),
+            order_id: 0,
+            side: OrderSide::Buy,
+            quantity: 0.002,
+            price: 20000.0,
+            commission: None,
+            commission_asset: None,
+        };
+        assert_eq!(event.to_string(), "Bought 0.002 BTCUSDT at 20000.");
+
+        let event = TradeEvent::TradeSkipped {
+            symbol: String::from("BTCUSDT"),
+            reason: SkipReason::PredictionBelowPrice {
+                predicted: 19000.0,
+                open: 20000.0,
+                close: 20100.0,
+            },
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"trade_skipped","symbol":"BTCUSDT","reason":{"reason":"prediction_below_price","predicted":19000.0,"open":20000.0,"close":20100.0}}"#
+        );
+        assert!(event.is_routine());
+
+        let event = TradeEvent::TradeSkipped {
+            symbol: String::from("BTCUSDT"),
+            reason: SkipReason::PriceAboveTarget {
+                predicted: 20500.0,
+                price: 20600.0,
+            },
+        };
+        assert_eq!(
+            event.to_string(),
+            "Skipping BTCUSDT trade: the price rose to 20600 while waiting for the approval, above the predicted value 20500."
+        );
+        assert!(!event.is_routine());
+    }
+}
@@ -0,0 +1,233 @@
+//! Technical indicators computed over a series of values, oldest first.
+//! Every function returns one value per input value, or `None` while the indicator is still warming up.
+
+/// Simple moving average over `period` values.
+pub fn sma(values: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut result = vec![None; values.len()];
+    if period == 0 {
+        return result;
+    }
+
+    let mut sum = 0.0;
+    for (i, value) in values.iter().enumerate() {
+        sum += value;
+        if i >= period {
+            sum -= values[i - period];
+        }
+        if i + 1 >= period {
+            result[i] = Some(sum / period as f64);
+        }
+    }
+
+    result
+}
+
+/// Exponential moving average over `period` values, seeded with the simple moving average of the first `period` values.
+pub fn ema(values: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut result = vec![None; values.len()];
+    if period == 0 || values.len() < period {
+        return result;
+    }
+
+    let alpha = 2.0 / (period as f64 + 1.0);
+    let mut ema = values[..period].iter().sum::<f64>() / period as f64;
+    result[period - 1] = Some(ema);
+
+    for i in period..values.len() {
+        ema = alpha * values[i] + (1.0 - alpha) * ema;
+        result[i] = Some(ema);
+    }
+
+    result
+}
+
+/// Relative strength index using Wilder's smoothing.
+pub fn rsi(closes: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut result = vec![None; closes.len()];
+    if period == 0 || closes.len() <= period {
+        return result;
+    }
+
+    let change = |i: usize| closes[i] - closes[i - 1];
+    let value = |gain: f64, loss: f64| {
+        if loss == 0.0 {
+            100.0
+        } else {
+            100.0 - 100.0 / (1.0 + gain / loss)
+        }
+    };
+
+    let mut gain = (1..=period).map(|i| change(i).max(0.0)).sum::<f64>() / period as f64;
+    let mut loss = (1..=period).map(|i| (-change(i)).max(0.0)).sum::<f64>() / period as f64;
+    result[period] = Some(value(gain, loss));
+
+    for (i, rsi) in result.iter_mut().enumerate().skip(period + 1) {
+        gain = (gain * (period - 1) as f64 + change(i).max(0.0)) / period as f64;
+        loss = (loss * (period - 1) as f64 + (-change(i)).max(0.0)) / period as f64;
+        *rsi = Some(value(gain, loss));
+    }
+
+    result
+}
+
+/// Moving average convergence divergence.
+/// Returns the MACD line, the signal line and the histogram.
+#[allow(clippy::type_complexity)]
+pub fn macd(
+    closes: &[f64],
+    fast: usize,
+    slow: usize,
+    signal: usize,
+) -> (Vec<Option<f64>>, Vec<Option<f64>>, Vec<Option<f64>>) {
+    let fast = ema(closes, fast);
+    let slow = ema(closes, slow);
+    let line: Vec<Option<f64>> = fast
+        .iter()
+        .zip(&slow)
+        .map(|(fast, slow)| Some((*fast)? - (*slow)?))
+        .collect();
+
+    // The signal line is an EMA over the defined part of the MACD line.
+    let start = line.iter().position(Option::is_some).unwrap_or(line.len());
+    let defined: Vec<f64> = line[start..].iter().map(|value| value.unwrap()).collect();
+    let mut signal_line = vec![None; start];
+    signal_line.extend(ema(&defined, signal));
+
+    let histogram = line
+        .iter()
+        .zip(&signal_line)
+        .map(|(line, signal)| Some((*line)? - (*signal)?))
+        .collect();
+
+    (line, signal_line, histogram)
+}
+
+/// Width of the Bollinger bands (`k` standard deviations around the SMA), relative to the SMA.
+pub fn bollinger_width(closes: &[f64], period: usize, k: f64) -> Vec<Option<f64>> {
+    sma(closes, period)
+        .iter()
+        .enumerate()
+        .map(|(i, mean)| {
+            let mean = (*mean)?;
+            let window = &closes[i + 1 - period..=i];
+            let variance = window.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / period as f64;
+            Some(2.0 * k * variance.sqrt() / mean)
+        })
+        .collect()
+}
+
+/// Average true range using Wilder's smoothing.
+pub fn atr(highs: &[f64], lows: &[f64], closes: &[f64], period: usize) -> Vec<Option<f64>> {
+    let mut result = vec![None; closes.len()];
+    if period == 0 || closes.len() < period {
+        return result;
+    }
+
+    let true_ranges: Vec<f64> = (0..closes.len())
+        .map(|i| {
+            let range = highs[i] - lows[i];
+            if i == 0 {
+                range
+            } else {
+                range
+                    .max((highs[i] - closes[i - 1]).abs())
+                    .max((lows[i] - closes[i - 1]).abs())
+            }
+        })
+        .collect();
+
+    let mut atr = true_ranges[..period].iter().sum::<f64>() / period as f64;
+    result[period - 1] = Some(atr);
+
+    for i in period..closes.len() {
+        atr = (atr * (period - 1) as f64 + true_ranges[i]) / period as f64;
+        result[i] = Some(atr);
+    }
+
+    result
+}
+
+/// Volume relative to its simple moving average over `period` candles.
+pub fn volume_ratio(volumes: &[f64], period: usize) -> Vec<Option<f64>> {
+    sma(volumes, period)
+        .iter()
+        .zip(volumes)
+        .map(|(mean, volume)| {
+            let mean = (*mean)?;
+            Some(if mean == 0.0 { 0.0 } else { volume / mean })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{atr, bollinger_width, ema, macd, rsi, sma, volume_ratio};
+
+    #[test]
+    fn test_sma() {
+        assert_eq!(
+            sma(&[1.0, 2.0, 3.0, 4.0], 2),
+            vec![None, Some(1.5), Some(2.5), Some(3.5)]
+        );
+        assert_eq!(sma(&[1.0], 2), vec![None]);
+    }
+
+    #[test]
+    fn test_ema() {
+        assert_eq!(
+            ema(&[1.0, 2.0, 3.0, 4.0], 3),
+            vec![None, None, Some(2.0), Some(3.0)]
+        );
+    }
+
+    #[test]
+    fn test_rsi() {
+        assert_eq!(rsi(&[1.0, 2.0, 3.0], 2), vec![None, None, Some(100.0)]);
+        assert_eq!(
+            rsi(&[1.0, 2.0, 1.0, 2.0], 2),
+            vec![None, None, Some(50.0), Some(75.0)]
+        );
+    }
+
+    #[test]
+    fn test_macd() {
+        let closes: Vec<f64> = (1..=6).map(f64::from).collect();
+        let (line, signal, histogram) = macd(&closes, 2, 3, 2);
+        assert_eq!(
+            line,
+            vec![None, None, Some(0.5), Some(0.5), Some(0.5), Some(0.5)]
+        );
+        assert_eq!(
+            signal,
+            vec![None, None, None, Some(0.5), Some(0.5), Some(0.5)]
+        );
+        assert_eq!(histogram[5], Some(0.0));
+    }
+
+    #[test]
+    fn test_bollinger_width() {
+        assert_eq!(
+            bollinger_width(&[1.0, 3.0, 2.0], 2, 2.0),
+            vec![None, Some(2.0), Some(0.8)]
+        );
+    }
+
+    #[test]
+    fn test_atr() {
+        let highs = [2.0, 3.0, 4.0];
+        let lows = [1.0, 2.0, 3.5];
+        let closes = [1.5, 2.5, 4.0];
+        assert_eq!(
+            atr(&highs, &lows, &closes, 2),
+            vec![None, Some(1.25), Some(1.375)]
+        );
+    }
+
+    #[test]
+    fn test_volume_ratio() {
+        assert_eq!(
+            volume_ratio(&[1.0, 3.0, 0.0, 0.0], 2),
+            vec![None, Some(1.5), Some(0.0), Some(0.0)]
+        );
+    }
+}
@@ -0,0 +1,397 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Write},
+    ops::{Bound, RangeBounds},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use csv::StringRecord;
+
+use crate::{
+    binance_market::{
+        BinanceKlineInterval, BinanceKlineOptions, BinanceMarket, BINANCE_MARKET_EPOCH,
+    },
+    dataset::Candle,
+};
+
+/// Keeps the closed candles of every symbol and interval in a CSV file on disk,
+/// so only the candles that aren't stored yet have to be fetched.
+pub struct KlineStore {
+    dir: PathBuf,
+    /// Gaps that binance has no candles for (e.g. exchange downtime), by file and start time.
+    /// These are only fetched once per run.
+    missing: Mutex<HashSet<(PathBuf, u64)>>,
+}
+
+impl KlineStore {
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+            missing: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Returns the file the candles of the given symbol and interval are stored in.
+    pub fn path(&self, symbol: &str, interval: BinanceKlineInterval) -> PathBuf {
+        self.dir
+            .join(format!("{}-{}.csv", symbol, interval.to_string()))
+    }
+
+    /// Reads the stored candles whose open time (in milliseconds) falls in the given range, oldest first.
+    /// The file is sorted by open time, so rows before the range are skipped without parsing them
+    /// and reading stops at the end of the range.
+    pub fn read<R: RangeBounds<u64>>(
+        &self,
+        symbol: &str,
+        interval: BinanceKlineInterval,
+        range: R,
+    ) -> anyhow::Result<Vec<Candle>> {
+        let path = self.path(symbol, interval);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .delimiter(b',')
+            .from_reader(file);
+
+        let mut candles = Vec::new();
+        let mut record = StringRecord::new();
+        while reader.read_record(&mut record)? {
+            let open_time: u64 = record[0].parse()?;
+            let past_end = match range.end_bound() {
+                Bound::Included(end) => open_time > *end,
+                Bound::Excluded(end) => open_time >= *end,
+                Bound::Unbounded => false,
+            };
+            if past_end {
+                break;
+            }
+            if range.contains(&open_time) {
+                candles.push(Candle::from_record(&record)?);
+            }
+        }
+
+        Ok(candles)
+    }
+
+    /// Fetches the candles from binance that are missing in the store.
+    /// Returns the amount of candles that were added.
+    pub fn update(
+        &self,
+        market: &BinanceMarket,
+        symbol: &str,
+        interval: BinanceKlineInterval,
+        now: u64,
+    ) -> anyhow::Result<usize> {
+        self.update_with(symbol, interval, now, |start, end| {
+            market
+                .get_klines(BinanceKlineOptions {
+                    pair: symbol.to_string(),
+                    interval,
+                    limit: None,
+                    start: Some(start),
+                    end,
+                })
+                .into_iter()
+                .map(|kline| kline.map(Candle::from))
+                .collect()
+        })
+    }
+
+    /// Fetches the gaps between stored candles and everything after the last one with `fetch`,
+    /// which is called with the start and optional end time (in milliseconds) of every range.
+    /// Candles that haven't closed yet at `now` are never stored.
+    /// When `fetch` fails, nothing is stored and the error is returned, so a failed update can simply be tried again.
+    pub fn update_with<F>(
+        &self,
+        symbol: &str,
+        interval: BinanceKlineInterval,
+        now: u64,
+        mut fetch: F,
+    ) -> anyhow::Result<usize>
+    where
+        F: FnMut(u64, Option<u64>) -> anyhow::Result<Vec<Candle>>,
+    {
+        let path = self.path(symbol, interval);
+        let mut candles: BTreeMap<u64, Candle> = self
+            .read(symbol, interval, ..)?
+            .into_iter()
+            .map(|candle| (candle.open_time, candle))
+            .collect();
+        let stored = candles.len();
+
+        // Every candle opens right after the previous one closed, anything in between is a gap.
+        let mut ranges: Vec<(u64, Option<u64>)> = candles
+            .values()
+            .zip(candles.values().skip(1))
+            .filter(|(previous, next)| next.open_time > previous.close_time + 1)
+            .map(|(previous, next)| (previous.close_time + 1, Some(next.open_time - 1)))
+            .filter(|(start, _)| {
+                !self
+                    .missing
+                    .lock()
+                    .unwrap()
+                    .contains(&(path.clone(), *start))
+            })
+            .collect();
+        // Candles after the last stored one can simply be appended to the file.
+        let tail_start = candles
+            .values()
+            .last()
+            .map(|candle| candle.close_time + 1)
+            .unwrap_or(BINANCE_MARKET_EPOCH);
+        ranges.push((tail_start, None));
+
+        let mut filled_gap = false;
+        for (start, end) in ranges {
+            debug!(
+                "Fetching {} {} klines from {} to {:?}.",
+                symbol,
+                interval.to_string(),
+                start,
+                end
+            );
+            let fetched = fetch(start, end)?;
+            if end.is_some() && fetched.is_empty() {
+                debug!("Binance has no klines from {} to {:?}.", start, end);
+                self.missing.lock().unwrap().insert((path.clone(), start));
+            }
+
+            for candle in fetched {
+                if candle.close_time < now && !candles.contains_key(&candle.open_time) {
+                    filled_gap |= candle.open_time < tail_start;
+                    candles.insert(candle.open_time, candle);
+                }
+            }
+        }
+
+        let added = candles.len() - stored;
+        if added > 0 {
+            fs::create_dir_all(&self.dir)?;
+            if filled_gap || stored == 0 {
+                write_csv(&path, symbol, candles.values())?;
+            } else {
+                append_csv(
+                    &path,
+                    symbol,
+                    candles.range(tail_start..).map(|(_, candle)| candle),
+                )?;
+            }
+        }
+
+        Ok(added)
+    }
+}
+
+/// Writes candles to a CSV file that `Candle::from_csv` can read.
+/// The file is written to a temporary file first and then moved in place, so it's never left half written.
+pub fn write_csv<'a, I>(path: &Path, symbol: &str, candles: I) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = &'a Candle>,
+{
+    let tmp_path = path.with_extension("tmp");
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b',')
+        .from_path(&tmp_path)?;
+
+    writer.write_record([
+        "open_time",
+        "close_time",
+        "symbol",
+        "open",
+        "high",
+        "low",
+        "close",
+        "volume",
+    ])?;
+    write_candles(&mut writer, symbol, candles)?;
+    drop(writer);
+
+    fs::rename(&tmp_path, path)?;
+
+    Ok(())
+}
+
+/// Adds candles to the end of a CSV file written by `write_csv`.
+fn append_csv<'a, I>(path: &Path, symbol: &str, candles: I) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = &'a Candle>,
+{
+    let file = OpenOptions::new().append(true).open(path)?;
+    let mut writer = csv::WriterBuilder::new().delimiter(b',').from_writer(file);
+    write_candles(&mut writer, symbol, candles)
+}
+
+fn write_candles<'a, W, I>(
+    writer: &mut csv::Writer<W>,
+    symbol: &str,
+    candles: I,
+) -> anyhow::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Candle>,
+{
+    for candle in candles {
+        writer.write_record(&[
+            candle.open_time.to_string(),
+            candle.close_time.to_string(),
+            symbol.to_string(),
+            candle.open.to_string(),
+            candle.high.to_string(),
+            candle.low.to_string(),
+            candle.close.to_string(),
+            candle.volume.to_string(),
+        ])?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use crate::{
+        binance_market::{BinanceKlineInterval, BINANCE_MARKET_EPOCH},
+        dataset::Candle,
+    };
+
+    use super::KlineStore;
+
+    const HOUR: u64 = 3_600_000;
+
+    fn candle(open_time: u64) -> Candle {
+        Candle {
+            open_time,
+            close_time: open_time + HOUR - 1,
+            open: 1.0,
+            high: 2.0,
+            low: 0.5,
+            close: 1.5,
+            volume: 10.0,
+        }
+    }
+
+    /// Hourly candles from `start` to `end`, like binance returns them.
+    fn candles(start: u64, end: Option<u64>, last: u64) -> Vec<Candle> {
+        (start..=end.unwrap_or(last).min(last))
+            .step_by(HOUR as usize)
+            .map(candle)
+            .collect()
+    }
+
+    #[test]
+    fn test_update() {
+        let dir = env::temp_dir().join("ml-crypto-trading-bot-test-klines");
+        let _ = fs::remove_dir_all(&dir);
+        let store = KlineStore::new(dir.to_str().unwrap());
+        let interval = BinanceKlineInterval::Hourly;
+        let start = BINANCE_MARKET_EPOCH;
+
+        // The first update fetches everything, except the candle that is still forming.
+        let mut calls = Vec::new();
+        let added = store
+            .update_with("BTCUSDT", interval, start + 3 * HOUR, |from, to| {
+                calls.push((from, to));
+                Ok(candles(from, to, start + 3 * HOUR))
+            })
+            .unwrap();
+        assert_eq!(added, 3);
+        assert_eq!(calls, vec![(start, None)]);
+
+        // Later updates only fetch what's new.
+        let mut calls = Vec::new();
+        let added = store
+            .update_with("BTCUSDT", interval, start + 5 * HOUR, |from, to| {
+                calls.push((from, to));
+                Ok(candles(from, to, start + 5 * HOUR))
+            })
+            .unwrap();
+        assert_eq!(added, 2);
+        assert_eq!(calls, vec![(start + 3 * HOUR, None)]);
+
+        let stored = store.read("BTCUSDT", interval, ..).unwrap();
+        assert_eq!(stored.len(), 5);
+        // The new candles were appended after the stored ones.
+        let csv = fs::read_to_string(store.path("BTCUSDT", interval)).unwrap();
+        assert_eq!(csv.lines().count(), 6);
+        assert_eq!(stored[0], candle(start));
+        assert!(store
+            .read("BTCUSDT", BinanceKlineInterval::Daily, ..)
+            .unwrap()
+            .is_empty());
+        let range = store
+            .read("BTCUSDT", interval, start + HOUR..start + 3 * HOUR)
+            .unwrap();
+        assert_eq!(range, vec![candle(start + HOUR), candle(start + 2 * HOUR)]);
+        let range = store
+            .read("BTCUSDT", interval, start + 3 * HOUR..=start + 4 * HOUR)
+            .unwrap();
+        assert_eq!(
+            range,
+            vec![candle(start + 3 * HOUR), candle(start + 4 * HOUR)]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_update_gaps() {
+        let dir = env::temp_dir().join("ml-crypto-trading-bot-test-klines-gaps");
+        let _ = fs::remove_dir_all(&dir);
+        let store = KlineStore::new(dir.to_str().unwrap());
+        let interval = BinanceKlineInterval::Hourly;
+        let start = BINANCE_MARKET_EPOCH;
+        let now = start + 6 * HOUR;
+
+        // Binance is missing the candles of the third and fourth hour the first time.
+        store
+            .update_with("BTCUSDT", interval, now, |from, to| {
+                let mut candles = candles(from, to, now);
+                candles.retain(|candle| {
+                    candle.open_time != start + 2 * HOUR && candle.open_time != start + 3 * HOUR
+                });
+                Ok(candles)
+            })
+            .unwrap();
+        assert_eq!(store.read("BTCUSDT", interval, ..).unwrap().len(), 4);
+
+        let mut calls = Vec::new();
+        let added = store
+            .update_with("BTCUSDT", interval, now, |from, to| {
+                calls.push((from, to));
+                Ok(candles(from, to, now))
+            })
+            .unwrap();
+        assert_eq!(added, 2);
+        assert_eq!(
+            calls,
+            vec![
+                (start + 2 * HOUR, Some(start + 4 * HOUR - 1)),
+                (start + 6 * HOUR, None)
+            ]
+        );
+        // The gap was filled in, so the file was rewritten in order.
+        let open_times: Vec<u64> = store
+            .read("BTCUSDT", interval, ..)
+            .unwrap()
+            .iter()
+            .map(|candle| candle.open_time)
+            .collect();
+        assert_eq!(
+            open_times,
+            candles(start, None, now - 1)
+                .iter()
+                .map(|candle| candle.open_time)
+                .collect::<Vec<_>>()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
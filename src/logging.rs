@@ -0,0 +1,108 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use log::{Log, Metadata, Record};
+
+use crate::utils::{format_timestamp, now};
+
+/// Amount of log lines kept for the `/logs` telegram command.
+pub const RECENT_LOG_LINES: usize = 100;
+
+/// The most recent log lines, oldest first.
+pub struct RecentLogs {
+    lines: Mutex<VecDeque<String>>,
+    capacity: usize,
+}
+
+impl RecentLogs {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    /// Adds a line, dropping the oldest one when full.
+    pub fn push(&self, line: String) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    /// Returns up to `count` of the most recent lines, oldest first.
+    pub fn tail(&self, count: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        lines
+            .iter()
+            .skip(lines.len().saturating_sub(count))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Logs through `env_logger` as configured by `RUST_LOG`, and keeps the lines it writes.
+struct Logger {
+    inner: env_logger::Logger,
+    recent: Arc<RecentLogs>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+
+        self.inner.log(record);
+        self.recent.push(format!(
+            "{} {} {}",
+            format_timestamp(now()),
+            record.level(),
+            record.args()
+        ));
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Installs the logger, like `env_logger::init`.
+/// Returns the lines it keeps, to be shown elsewhere.
+pub fn init() -> Arc<RecentLogs> {
+    let recent = Arc::new(RecentLogs::new(RECENT_LOG_LINES));
+    let inner = env_logger::Builder::from_default_env().build();
+
+    log::set_max_level(inner.filter());
+    log::set_boxed_logger(Box::new(Logger {
+        inner,
+        recent: recent.clone(),
+    }))
+    .expect("a logger was already installed");
+
+    recent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RecentLogs;
+
+    #[test]
+    fn test_recent_logs() {
+        let logs = RecentLogs::new(2);
+        assert!(logs.tail(5).is_empty());
+
+        logs.push(String::from("a"));
+        logs.push(String::from("b"));
+        logs.push(String::from("c"));
+        assert_eq!(logs.tail(5), vec!["b", "c"]);
+        assert_eq!(logs.tail(1), vec!["c"]);
+    }
+}
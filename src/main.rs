@@ -1,104 +1,314 @@
+use crate::backtest::Backtest;
 use crate::binance_market::BinanceMarket;
-use crate::config::TelegramConfig;
-use crate::config::{try_load_config, DEFAULT_CONFIG};
+use crate::cli::{Cli, Command};
+use crate::config::{try_load_config, AppConfig};
+use crate::control::Controls;
+use crate::dataset::{Candle, DataSet};
+use crate::events::TradeEvent;
+use crate::kline_store::{write_csv, KlineStore};
+use crate::logging::RecentLogs;
+use crate::model::Model;
+use crate::model_store::{ModelMetrics, ModelStore, TrainedModel};
+use crate::notifications::{NotificationSender, NOTIFICATION_BUFFER};
+use crate::notifier::Notifier;
+use crate::shutdown::Shutdown;
+use crate::status::Status;
 use crate::strategy::Strategy;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{channel, Receiver};
+use crate::utils::now;
+use anyhow::{anyhow, Context};
+use clap::Parser;
+use std::path::Path;
 use std::sync::Arc;
 use std::thread;
 use strategy::LightGBMStrategy;
-use teloxide::requests::Requester;
-use teloxide::types::Message;
-use teloxide::Bot;
+use telegram::{start_telegram_bot, TelegramNotifier};
 
 #[macro_use]
 extern crate log;
 
+pub mod backtest;
 pub mod binance_market;
 pub mod binance_strategy;
+pub mod cli;
 pub mod config;
+pub mod control;
 pub mod dataset;
+pub mod events;
+pub mod indicators;
+pub mod kline_store;
+pub mod logging;
 pub mod market;
 pub mod model;
+pub mod model_store;
+pub mod notifications;
+pub mod notifier;
+pub mod position;
+pub mod rate_limiter;
+pub mod retry;
+pub mod scheduler;
+pub mod shutdown;
+pub mod simulated_market;
+pub mod status;
 pub mod strategy;
+pub mod symbol_filters;
+pub mod telegram;
 pub mod utils;
 
-async fn start_telegram_bot(
-    config: TelegramConfig,
-    notification_rx: Receiver<String>,
-    running: Arc<AtomicBool>,
-) {
-    info!("Starting telegram bot.");
-
-    let telegram_bot = Bot::new(config.bot_token);
-    let tb = telegram_bot.clone();
-
-    let handle_listener = tokio::spawn(async {
-        teloxide::repl(tb, |bot: Bot, msg: Message| async move {
-            // TODO: allow user to query current status and/or logs of the trading bot?
-            bot.send_message(
-                msg.chat.id,
-                format!(
-                    "Sorry, I don't support any commands yet. Chat ID: {}",
-                    msg.chat.id
-                ),
-            )
-            .await?;
-            Ok(())
-        })
-        .await;
-    });
+/// Reads the klines from the given CSV file, or the stored klines of the configured symbol and interval.
+fn load_candles(config: &AppConfig, csv_file_path: Option<&str>) -> anyhow::Result<Vec<Candle>> {
+    match csv_file_path {
+        Some(csv_file_path) => Candle::from_csv(csv_file_path),
+        None => {
+            KlineStore::new(&config.dataset.kline_dir).read(&config.symbol, config.interval, ..)
+        }
+    }
+}
 
-    let handle_sender = tokio::spawn(async move {
-        while running.load(Ordering::SeqCst) {
-            let msg = notification_rx.recv().unwrap();
+/// Replays the klines through the strategy and prints the results.
+fn run_backtest(config: AppConfig, csv_file_path: Option<&str>) -> anyhow::Result<()> {
+    let candles = load_candles(&config, csv_file_path)?;
+    info!(
+        "Backtesting {} on {} candles.",
+        config.symbol,
+        candles.len()
+    );
 
-            telegram_bot
-                .send_message(config.chat_id.to_string(), msg)
-                .await
-                .unwrap();
-        }
-    });
+    let model = Model::with_params(&config.model);
+    let report = Backtest::new(&config, &model).run(&candles)?;
+
+    println!("{}", report);
+    Ok(())
+}
+
+/// Fetches the klines of the given symbol that aren't stored yet, and optionally exports all of them to a CSV file.
+fn download(
+    config: AppConfig,
+    symbol: Option<String>,
+    interval: Option<&str>,
+    csv_file_path: Option<&str>,
+) -> anyhow::Result<()> {
+    let symbol = symbol.unwrap_or(config.symbol);
+    let interval = match interval {
+        Some(interval) => interval.parse()?,
+        None => config.interval,
+    };
+    // Nothing interrupts a download, other than terminating the program.
+    let market = BinanceMarket::new(config.binance, Arc::new(Shutdown::new()));
+    let store = KlineStore::new(&config.dataset.kline_dir);
+
+    let added = store.update(&market, &symbol, interval, now().as_millis() as u64)?;
+    println!(
+        "Stored {} new klines of {} in {}.",
+        added,
+        symbol,
+        store.path(&symbol, interval).display()
+    );
+
+    if let Some(csv_file_path) = csv_file_path {
+        let candles = store.read(&symbol, interval, ..)?;
+        write_csv(Path::new(csv_file_path), &symbol, &candles)?;
+        println!("Saved {} klines to {}.", candles.len(), csv_file_path);
+    }
+
+    Ok(())
+}
+
+/// Trains a model on the klines, limited to the training window, and saves it.
+fn train(config: AppConfig, csv_file_path: Option<&str>) -> anyhow::Result<()> {
+    let mut candles = load_candles(&config, csv_file_path)?;
+    if let Some(window) = config.training.window {
+        candles = candles.split_off(candles.len().saturating_sub(window as usize));
+    }
+
+    let model = Model::with_params(&config.model);
+    let trained = TrainedModel::train(&model, &config.symbol, &config.dataset, &candles)?;
+    ModelStore::new(&config.training.model_dir).save(&trained)?;
+
+    let metadata = &trained.metadata;
+    println!(
+        "Saved model {} trained on {} samples (rmse: {}, mae: {}).",
+        metadata.version, metadata.metrics.samples, metadata.metrics.rmse, metadata.metrics.mae
+    );
+    Ok(())
+}
+
+/// Loads the given model version, or the latest model of the configured symbol.
+fn load_model(config: &AppConfig, version: Option<String>) -> anyhow::Result<TrainedModel> {
+    let store = ModelStore::new(&config.training.model_dir);
+    match version.or(config.training.model.clone()) {
+        Some(version) => store.load(&version),
+        None => store.latest(&config.symbol)?.ok_or(anyhow!(
+            "no saved model of {} in {}, train one first",
+            config.symbol,
+            config.training.model_dir
+        )),
+    }
+}
+
+/// Predicts the label of the candle after the last one.
+fn predict(
+    config: AppConfig,
+    csv_file_path: Option<&str>,
+    version: Option<String>,
+) -> anyhow::Result<()> {
+    let model = load_model(&config, version)?;
+    let candles = load_candles(&config, csv_file_path)?;
+    let last = candles.last().ok_or(anyhow!("no klines to predict from"))?;
 
-    let _ = tokio::join!(handle_listener);
+    let features = DataSet::latest_features(&candles, &model.metadata.pipeline()?)
+        .ok_or(anyhow!("not enough candles to compute the features"))?;
+    let prediction = model.booster.predict(vec![features])?;
 
-    handle_sender.abort(); // TODO: find better way to stop sending messages on quit.
+    println!(
+        "Model {} predicts {:?}: {} (last close: {}).",
+        model.metadata.version, model.metadata.label, prediction[0][0], last.close
+    );
+    Ok(())
 }
 
-#[tokio::main]
-async fn main() {
-    env_logger::init();
+/// Compares the predictions of a saved model to the klines.
+fn evaluate(
+    config: AppConfig,
+    csv_file_path: Option<&str>,
+    version: Option<String>,
+) -> anyhow::Result<()> {
+    let model = load_model(&config, version)?;
+    let candles = load_candles(&config, csv_file_path)?;
+
+    let dataset = DataSet::new(&candles, &model.metadata.label, &model.metadata.pipeline()?);
+    let metrics = ModelMetrics::evaluate(&model.booster, &dataset)?;
+
+    println!(
+        "Model {} on {} samples: rmse {}, mae {} (training: rmse {}, mae {}).",
+        model.metadata.version,
+        metrics.samples,
+        metrics.rmse,
+        metrics.mae,
+        model.metadata.metrics.rmse,
+        model.metadata.metrics.mae
+    );
+    Ok(())
+}
+
+/// Trades until a shutdown is requested.
+/// Calls binance with blocking requests, so it must not run on the async runtime.
+fn trade(
+    config: AppConfig,
+    shutdown: Arc<Shutdown>,
+    status: Arc<Status>,
+    controls: Controls,
+    tx: &NotificationSender<TradeEvent>,
+) -> anyhow::Result<()> {
+    // Fail at startup when binance would reject every buy order, rather than on the first trade.
+    let market = BinanceMarket::new(config.binance.clone(), shutdown.clone());
+    market
+        .symbol_filters(&config.symbol)?
+        .market_buy_amount(config.trade.amount)
+        .context("invalid trade amount")?;
+
+    if config.trade.test {
+        warn!("Bot is running in test mode. No real funds will be spent.");
+    } else {
+        warn!("Bot is running in production mode. Real funds will be spent!");
+    }
+
+    let strategy = LightGBMStrategy::new(config, market, shutdown, status, controls);
+    strategy.execute(tx);
+    Ok(())
+}
 
-    let (notification_tx, notification_rx) = channel::<String>();
+/// Trades live, sends notifications and runs the telegram bot until the program is terminated.
+async fn run(config: AppConfig, logs: Arc<RecentLogs>) -> anyhow::Result<()> {
+    let (notification_tx, _) = notifications::channel(NOTIFICATION_BUFFER);
+    let (control_tx, controls) = control::channel();
 
-    let config = try_load_config(DEFAULT_CONFIG);
-    let running = Arc::new(AtomicBool::new(true));
+    let shutdown = Arc::new(Shutdown::new());
+    let status = Arc::new(Status::new(&config.symbol, config.trade.test));
     let telegram_config = config.telegram.clone();
 
-    let r = running.clone();
+    // Subscribed before the strategy starts, so no notifier misses its first events.
+    let mut notifiers: Vec<(Box<dyn Notifier>, bool)> = Vec::new();
+    if let Some(telegram_config) = &telegram_config {
+        notifiers.push((Box::new(TelegramNotifier::new(telegram_config)), false));
+    }
+    for notifier_config in &config.notifiers {
+        notifiers.push((
+            notifier::from_config(notifier_config),
+            notifier_config.routine,
+        ));
+    }
+    let handle_notifiers = notifiers
+        .into_iter()
+        .map(|(notifier, routine)| {
+            tokio::spawn(notifier::run(
+                notifier,
+                routine,
+                notification_tx.subscribe(),
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let s = shutdown.clone();
     ctrlc::set_handler(move || {
         println!("Exiting program.");
-        r.store(false, Ordering::SeqCst);
+        s.trigger();
     })
     .expect("Error setting Ctrl-C handler");
 
-    let r = running.clone();
+    let s = shutdown.clone();
+    let st = status.clone();
     let handle_trading_bot = thread::spawn(move || {
-        if config.trade.test {
-            warn!("Bot is running in test mode. No real funds will be spent.");
-        } else {
-            warn!("Bot is running in production mode. Real funds will be spent!");
+        // The strategy may also stop by itself, fail to start or panic, take the telegram bot down with it.
+        let _guard = s.trigger_on_drop();
+        trade(config, s, st, controls, &notification_tx)
+    });
+
+    match telegram_config {
+        Some(telegram_config) => {
+            start_telegram_bot(telegram_config, shutdown, status, logs, control_tx).await
         }
+        None => shutdown.wait().await,
+    }
 
-        let market = BinanceMarket::new(config.binance.clone());
-        let strategy = LightGBMStrategy::new(config, market);
-        strategy.execute(r, &notification_tx);
-    });
+    // Joining blocks until the strategy noticed the shutdown, which must not hold up the notifiers.
+    let result = match tokio::task::spawn_blocking(move || handle_trading_bot.join()).await {
+        Ok(Ok(result)) => result,
+        _ => Err(anyhow!("the trading bot panicked")),
+    };
+
+    // The notifiers stop once they've sent what the strategy reported before it stopped.
+    for handle in handle_notifiers {
+        let _ = handle.await;
+    }
+    result
+}
+
+fn main() {
+    let logs = logging::init();
+
+    let cli = Cli::parse();
+    let config = try_load_config(&cli.config);
 
-    let r = running.clone();
-    start_telegram_bot(telegram_config, notification_rx, r).await;
+    let result = match cli.command.unwrap_or(Command::Run) {
+        // Only trading needs the async runtime, everything else is blocking code that must not run on it.
+        Command::Run => tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .context("failed to start the async runtime")
+            .and_then(|runtime| runtime.block_on(run(config, logs))),
+        Command::Download {
+            symbol,
+            interval,
+            out,
+        } => download(config, symbol, interval.as_deref(), out.as_deref()),
+        Command::Train { data } => train(config, data.as_deref()),
+        Command::Predict { data, model } => predict(config, data.as_deref(), model),
+        Command::Backtest { data } => run_backtest(config, data.as_deref()),
+        Command::Evaluate { data, model } => evaluate(config, data.as_deref(), model),
+    };
 
-    handle_trading_bot
-        .join()
-        .expect("Failed to join the trading bot thread.");
+    if let Err(err) = result {
+        eprintln!("error: {:#}", err);
+        std::process::exit(1);
+    }
 }
@@ -1,13 +1,205 @@
+use std::fmt;
+
 use anyhow::Result;
+use serde::Serialize;
+
+/// Details about an executed market order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderFill {
+    /// ID of the order on the exchange, 0 for test orders.
+    pub order_id: u64,
+
+    /// Base asset quantity that was bought or sold.
+    /// For buys, commission paid in the base asset has already been subtracted, so this is exactly what can be sold again.
+    pub quantity: f64,
+
+    /// Average price of all fills.
+    pub price: f64,
+
+    /// Total commission paid.
+    pub commission: f64,
+
+    /// Asset the commission was paid in.
+    pub commission_asset: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderSide {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderType {
+    Market,
+    Limit,
+    /// Becomes a market order once the stop price is reached.
+    StopLoss,
+    /// Becomes a limit order once the stop price is reached.
+    StopLossLimit,
+    TakeProfit,
+    TakeProfitLimit,
+    /// The limit leg of an OCO order, which is rejected instead of filled immediately when it would match right away.
+    LimitMaker,
+}
+
+impl fmt::Display for OrderSide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OrderSide::Buy => "buy",
+            OrderSide::Sell => "sell",
+        })
+    }
+}
+
+impl fmt::Display for OrderType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OrderType::Market => "market",
+            OrderType::Limit => "limit",
+            OrderType::StopLoss => "stop-loss",
+            OrderType::StopLossLimit => "stop-loss limit",
+            OrderType::TakeProfit => "take-profit",
+            OrderType::TakeProfitLimit => "take-profit limit",
+            OrderType::LimitMaker => "limit maker",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    New,
+    PartiallyFilled,
+    Filled,
+    Canceled,
+    Rejected,
+    Expired,
+}
+
+impl OrderStatus {
+    /// Returns whether the order is still on the book and may be (further) filled.
+    pub fn is_open(&self) -> bool {
+        matches!(self, OrderStatus::New | OrderStatus::PartiallyFilled)
+    }
+}
+
+/// Handle of an order that was placed, with its state at the time it was queried.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Order {
+    /// ID of the order on the exchange, 0 for test orders.
+    pub id: u64,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub order_type: OrderType,
+    pub status: OrderStatus,
+    /// Limit price.
+    pub price: f64,
+    /// Price at which a stop order turns into a limit order.
+    pub stop_price: Option<f64>,
+    /// Base asset quantity of the order.
+    pub quantity: f64,
+    /// Base asset quantity filled so far.
+    pub executed_quantity: f64,
+    /// Quote asset quantity filled so far.
+    pub quote_quantity: f64,
+}
+
+impl Order {
+    /// Returns the average price of the filled part of the order.
+    pub fn average_price(&self) -> Option<f64> {
+        if self.executed_quantity > 0.0 {
+            Some(self.quote_quantity / self.executed_quantity)
+        } else {
+            None
+        }
+    }
+}
+
+/// A limit order and a stop-limit order of which only one can fill: when one of them does, the other one is canceled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OcoOrder {
+    /// ID of the order list on the exchange.
+    pub id: u64,
+    /// Leg that fills at the limit price (e.g. the take-profit of a sell).
+    pub limit: Order,
+    /// Leg that is triggered at the stop price (e.g. the stop-loss of a sell).
+    pub stop: Order,
+}
+
+/// Amount of an asset held in the account.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Balance {
+    pub asset: String,
+    /// Available for new orders.
+    pub free: f64,
+    /// Reserved for open orders.
+    pub locked: f64,
+}
+
+impl Balance {
+    pub fn total(&self) -> f64 {
+        self.free + self.locked
+    }
+}
 
 /// Shared trait to be implemented by all supported markets.
 pub trait Market {
     /// Returns the current price of the specified symbol or pair.
     fn get_price(&self, symbol: &str) -> Result<f64>;
 
-    /// Places a buy order on the market.
-    fn place_buy_order(&self, symbol: &str, quantity: f64, test: bool) -> anyhow::Result<()>;
+    /// Places a market buy order spending `quantity` of the quote asset.
+    fn place_buy_order(&self, symbol: &str, quantity: f64, test: bool) -> Result<OrderFill>;
+
+    /// Places a market sell order selling `quantity` of the base asset.
+    fn place_sell_order(&self, symbol: &str, quantity: f64, test: bool) -> Result<OrderFill>;
+
+    /// Places a good-till-canceled limit order for `quantity` of the base asset at `price`.
+    fn place_limit_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        price: f64,
+        test: bool,
+    ) -> Result<Order>;
+
+    /// Places an order that becomes a limit order at `price` once `stop_price` is reached.
+    fn place_stop_limit_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        stop_price: f64,
+        price: f64,
+        test: bool,
+    ) -> Result<Order>;
+
+    /// Places a limit order at `price` together with a stop-limit order at `stop_price` / `stop_limit_price`.
+    /// There is no test mode, as binance doesn't offer one for OCO orders.
+    fn place_oco_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        price: f64,
+        stop_price: f64,
+        stop_limit_price: f64,
+    ) -> Result<OcoOrder>;
+
+    /// Returns the current state of an order.
+    fn get_order(&self, symbol: &str, id: u64) -> Result<Order>;
+
+    /// Cancels an open order and returns its final state.
+    fn cancel_order(&self, symbol: &str, id: u64) -> Result<Order>;
+
+    /// Returns all open orders of a symbol.
+    fn open_orders(&self, symbol: &str) -> Result<Vec<Order>>;
+
+    /// Returns the balance of an asset, which is zero when it isn't held at all.
+    fn get_balance(&self, asset: &str) -> Result<Balance>;
 
-    /// Places a sell order on the market.
-    fn place_sell_order(&self, symbol: &str, quantity: f64, test: bool) -> anyhow::Result<()>;
+    /// Returns the balances of all assets that are held.
+    fn get_balances(&self) -> Result<Vec<Balance>>;
 }
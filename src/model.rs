@@ -1,13 +1,294 @@
+use anyhow::anyhow;
 use lightgbm::Error;
-use serde_json::{json, Value};
+use serde_json::{json, Map, Value};
 
 use crate::dataset::DataSet;
 
+/// LightGBM parameters by name, see https://lightgbm.readthedocs.io/en/v3.3.2/Parameters.html.
+pub type ModelParams = Map<String, Value>;
+
+/// The kind of value a LightGBM parameter accepts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ParamKind {
+    Str,
+    Bool,
+    Int,
+    /// An integer of at least 1.
+    PositiveInt,
+    Float,
+    /// A float of at least 0.
+    NonNegative,
+    /// A float in the range (0, 1].
+    Fraction,
+    /// A list of values, or a string of comma separated values.
+    List,
+}
+
+/// LightGBM parameters that are checked before training, by their canonical name and with their aliases.
+/// Others are passed to LightGBM as they are.
+const PARAMS: &[(&str, &[&str], ParamKind)] = &[
+    // Core parameters.
+    (
+        "objective",
+        &["objective_type", "app", "application", "loss"],
+        ParamKind::Str,
+    ),
+    ("boosting", &["boosting_type", "boost"], ParamKind::Str),
+    (
+        "num_iterations",
+        &[
+            "num_iteration",
+            "n_iter",
+            "num_tree",
+            "num_trees",
+            "num_round",
+            "num_rounds",
+            "nrounds",
+            "num_boost_round",
+            "n_estimators",
+            "max_iter",
+        ],
+        ParamKind::PositiveInt,
+    ),
+    (
+        "learning_rate",
+        &["shrinkage_rate", "eta"],
+        ParamKind::NonNegative,
+    ),
+    (
+        "num_leaves",
+        &["num_leaf", "max_leaves", "max_leaf", "max_leaf_nodes"],
+        ParamKind::PositiveInt,
+    ),
+    (
+        "tree_learner",
+        &["tree", "tree_type", "tree_learner_type"],
+        ParamKind::Str,
+    ),
+    (
+        "num_threads",
+        &["num_thread", "nthread", "nthreads", "n_jobs"],
+        ParamKind::Int,
+    ),
+    ("device_type", &["device"], ParamKind::Str),
+    ("seed", &["random_seed", "random_state"], ParamKind::Int),
+    ("deterministic", &[], ParamKind::Bool),
+    // Learning control parameters.
+    ("force_col_wise", &[], ParamKind::Bool),
+    ("force_row_wise", &[], ParamKind::Bool),
+    ("max_depth", &[], ParamKind::Int),
+    (
+        "min_data_in_leaf",
+        &[
+            "min_data_per_leaf",
+            "min_data",
+            "min_child_samples",
+            "min_samples_leaf",
+        ],
+        ParamKind::Int,
+    ),
+    (
+        "min_sum_hessian_in_leaf",
+        &[
+            "min_sum_hessian_per_leaf",
+            "min_sum_hessian",
+            "min_hessian",
+            "min_child_weight",
+        ],
+        ParamKind::NonNegative,
+    ),
+    (
+        "bagging_fraction",
+        &["sub_row", "subsample", "bagging"],
+        ParamKind::Fraction,
+    ),
+    (
+        "pos_bagging_fraction",
+        &["pos_sub_row", "pos_subsample", "pos_bagging"],
+        ParamKind::Fraction,
+    ),
+    (
+        "neg_bagging_fraction",
+        &["neg_sub_row", "neg_subsample", "neg_bagging"],
+        ParamKind::Fraction,
+    ),
+    ("bagging_freq", &["subsample_freq"], ParamKind::Int),
+    ("bagging_seed", &["bagging_fraction_seed"], ParamKind::Int),
+    (
+        "feature_fraction",
+        &["sub_feature", "colsample_bytree"],
+        ParamKind::Fraction,
+    ),
+    (
+        "feature_fraction_bynode",
+        &["sub_feature_bynode", "colsample_bynode"],
+        ParamKind::Fraction,
+    ),
+    ("feature_fraction_seed", &[], ParamKind::Int),
+    ("extra_trees", &["extra_tree"], ParamKind::Bool),
+    ("extra_seed", &[], ParamKind::Int),
+    (
+        "early_stopping_round",
+        &[
+            "early_stopping_rounds",
+            "early_stopping",
+            "n_iter_no_change",
+        ],
+        ParamKind::Int,
+    ),
+    (
+        "max_delta_step",
+        &["max_tree_output", "max_leaf_output"],
+        ParamKind::Float,
+    ),
+    (
+        "lambda_l1",
+        &["reg_alpha", "l1_regularization"],
+        ParamKind::NonNegative,
+    ),
+    (
+        "lambda_l2",
+        &["reg_lambda", "lambda", "l2_regularization"],
+        ParamKind::NonNegative,
+    ),
+    ("linear_lambda", &[], ParamKind::NonNegative),
+    (
+        "min_gain_to_split",
+        &["min_split_gain"],
+        ParamKind::NonNegative,
+    ),
+    ("drop_rate", &["rate_drop"], ParamKind::Fraction),
+    ("max_drop", &[], ParamKind::Int),
+    ("skip_drop", &[], ParamKind::NonNegative),
+    ("xgboost_dart_mode", &[], ParamKind::Bool),
+    ("uniform_drop", &[], ParamKind::Bool),
+    ("drop_seed", &[], ParamKind::Int),
+    ("top_rate", &[], ParamKind::NonNegative),
+    ("other_rate", &[], ParamKind::NonNegative),
+    ("path_smooth", &[], ParamKind::NonNegative),
+    (
+        "monotone_constraints",
+        &["mc", "monotone_constraint", "monotonic_cst"],
+        ParamKind::List,
+    ),
+    (
+        "monotone_constraints_method",
+        &["monotone_constraining_method", "mc_method"],
+        ParamKind::Str,
+    ),
+    ("interaction_constraints", &[], ParamKind::List),
+    ("verbosity", &["verbose"], ParamKind::Int),
+    // Dataset parameters.
+    ("linear_tree", &["linear_trees"], ParamKind::Bool),
+    ("max_bin", &["max_bins"], ParamKind::PositiveInt),
+    ("max_bin_by_feature", &[], ParamKind::PositiveInt),
+    ("min_data_in_bin", &[], ParamKind::PositiveInt),
+    (
+        "bin_construct_sample_cnt",
+        &["subsample_for_bin"],
+        ParamKind::PositiveInt,
+    ),
+    ("data_random_seed", &["data_seed"], ParamKind::Int),
+    ("use_missing", &[], ParamKind::Bool),
+    ("zero_as_missing", &[], ParamKind::Bool),
+    // Objective parameters.
+    ("num_class", &["num_classes"], ParamKind::PositiveInt),
+    (
+        "is_unbalance",
+        &["unbalance", "unbalanced_sets"],
+        ParamKind::Bool,
+    ),
+    ("scale_pos_weight", &[], ParamKind::NonNegative),
+    ("sigmoid", &[], ParamKind::NonNegative),
+    ("boost_from_average", &[], ParamKind::Bool),
+    ("reg_sqrt", &[], ParamKind::Bool),
+    ("alpha", &[], ParamKind::NonNegative),
+    ("fair_c", &[], ParamKind::NonNegative),
+    ("poisson_max_delta_step", &[], ParamKind::NonNegative),
+    ("tweedie_variance_power", &[], ParamKind::NonNegative),
+    // Metric parameters.
+    ("metric", &["metrics", "metric_types"], ParamKind::Str),
+    ("first_metric_only", &[], ParamKind::Bool),
+];
+
+/// Returns the canonical name and kind of a known parameter, looked up by its name or any of its aliases.
+fn find_param(name: &str) -> Option<(&'static str, ParamKind)> {
+    PARAMS
+        .iter()
+        .find(|(param, aliases, _)| *param == name || aliases.contains(&name))
+        .map(|(param, _, kind)| (*param, *kind))
+}
+
+/// Returns the canonical name of a parameter, unknown parameters keep their name.
+fn canonical_name(name: &str) -> &str {
+    find_param(name).map_or(name, |(param, _)| param)
+}
+
+/// Checks that the known parameters have a valid value and aren't set twice under different aliases.
+/// Unknown parameters are left for LightGBM to check.
+pub fn validate_params(params: &ModelParams) -> anyhow::Result<()> {
+    let mut seen: Vec<(&str, &str)> = Vec::new();
+    for (name, value) in params {
+        let (canonical, kind) = match find_param(name) {
+            Some(param) => param,
+            None => {
+                warn!(
+                    "Unknown model parameter {}, passing it to LightGBM as is.",
+                    name
+                );
+                continue;
+            }
+        };
+        if let Some((other, _)) = seen.iter().find(|(_, param)| *param == canonical) {
+            return Err(anyhow!(
+                "model parameters {} and {} both set {}",
+                other,
+                name,
+                canonical
+            ));
+        }
+        seen.push((name, canonical));
+
+        let valid = match kind {
+            ParamKind::Str => value.is_string(),
+            ParamKind::Bool => value.is_boolean(),
+            ParamKind::Int => value.is_i64(),
+            ParamKind::PositiveInt => matches!(value.as_i64(), Some(value) if value >= 1),
+            ParamKind::Float => value.is_number(),
+            ParamKind::NonNegative => matches!(value.as_f64(), Some(value) if value >= 0.0),
+            ParamKind::Fraction => {
+                matches!(value.as_f64(), Some(value) if value > 0.0 && value <= 1.0)
+            }
+            ParamKind::List => value.is_array() || value.is_string(),
+        };
+        if !valid {
+            return Err(anyhow!(
+                "invalid value {} for model parameter {}, expected {}",
+                value,
+                name,
+                match kind {
+                    ParamKind::Str => "a string",
+                    ParamKind::Bool => "true or false",
+                    ParamKind::Int => "an integer",
+                    ParamKind::PositiveInt => "an integer of at least 1",
+                    ParamKind::Float => "a number",
+                    ParamKind::NonNegative => "a number of at least 0",
+                    ParamKind::Fraction => "a number between 0 (exclusive) and 1",
+                    ParamKind::List => "a list or a comma separated string",
+                }
+            ));
+        }
+    }
+
+    Ok(())
+}
+
 pub struct Model {
     params: Value,
 }
 
 impl Model {
+    /// Creates a model with the default parameters.
     pub fn new() -> Self {
         let params = json! {
             {
@@ -19,25 +300,48 @@ impl Model {
                 "lambda_l1": 0.0120496605030283,
                 "lambda_l2": 0.139677140815755,
                 "max_bin_by_feature": 1033,
-                "verbose": -1
+                "verbosity": -1
             }
         };
 
         Model { params }
     }
 
+    /// Creates a model with the given parameters merged over the defaults, under their canonical name.
+    /// The parameters should have been checked with `validate_params` first.
+    pub fn with_params(overrides: &ModelParams) -> Self {
+        let mut params = Self::new().params;
+        for (name, value) in overrides {
+            params[canonical_name(name)] = value.clone();
+        }
+        info!("Model parameters: {}", params);
+
+        Model { params }
+    }
+
+    /// The effective LightGBM parameters.
+    pub fn params(&self) -> &Value {
+        &self.params
+    }
+
     /// Retrain the model with the given dataset.
     pub fn train(&self, DataSet(features, labels): DataSet) -> Result<lightgbm::Booster, Error> {
-        let train_dataset = lightgbm::Dataset::from_mat(features, labels).unwrap();
+        let train_dataset = lightgbm::Dataset::from_mat(features, labels)?;
         lightgbm::Booster::train(train_dataset, &self.params)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use serde_json::json;
+
     use crate::dataset::DataSet;
 
-    use super::Model;
+    use super::{validate_params, Model, ModelParams};
+
+    fn params(value: serde_json::Value) -> ModelParams {
+        value.as_object().unwrap().clone()
+    }
 
     #[test]
     fn test_train() {
@@ -48,4 +352,49 @@ mod tests {
         let score = prediction[0][0];
         assert_eq!(score, 1.0);
     }
+
+    #[test]
+    fn test_with_params() {
+        let model = Model::with_params(&params(json!({ "num_leaves": 31, "seed": 42 })));
+        assert_eq!(model.params()["num_leaves"], 31);
+        assert_eq!(model.params()["seed"], 42);
+        assert_eq!(model.params()["num_iterations"], 1000);
+        assert_eq!(model.params()["objective"], "regression");
+
+        // Aliases override the defaults they stand for.
+        let model = Model::with_params(&params(json!({ "n_estimators": 200, "reg_alpha": 0.5 })));
+        assert_eq!(model.params()["num_iterations"], 200);
+        assert_eq!(model.params()["lambda_l1"], 0.5);
+        assert_eq!(model.params().get("n_estimators"), None);
+    }
+
+    #[test]
+    fn test_validate_params() {
+        assert!(validate_params(&params(json!({
+            "objective": "huber",
+            "learning_rate": 0.05,
+            "lambda_l1": 0,
+            "bagging_fraction": 1,
+            "extra_trees": true
+        })))
+        .is_ok());
+        assert!(validate_params(&params(json!({
+            "n_estimators": 500,
+            "min_child_samples": 20,
+            "num_class": 3,
+            "monotone_constraints": [1, 0, -1],
+            "cegb_tradeoff": 1.0
+        })))
+        .is_ok());
+        assert!(validate_params(&params(json!({ "n_estimators": 0 }))).is_err());
+        assert!(validate_params(&params(json!({
+            "num_iterations": 100,
+            "n_estimators": 200
+        })))
+        .is_err());
+        assert!(validate_params(&params(json!({ "num_leaves": 0 }))).is_err());
+        assert!(validate_params(&params(json!({ "num_leaves": "31" }))).is_err());
+        assert!(validate_params(&params(json!({ "bagging_fraction": 1.5 }))).is_err());
+        assert!(validate_params(&params(json!({ "extra_trees": 1 }))).is_err());
+    }
 }
@@ -0,0 +1,304 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use anyhow::anyhow;
+use lightgbm::Booster;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    config::DataSetConfig,
+    dataset::{Candle, DataSet, FeaturePipeline, Label},
+    model::Model,
+    utils::now,
+};
+
+/// How well a model fits the data it was trained on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelMetrics {
+    /// Amount of samples the model was trained on.
+    pub samples: usize,
+    /// Root mean squared error.
+    pub rmse: f64,
+    /// Mean absolute error.
+    pub mae: f64,
+}
+
+impl ModelMetrics {
+    /// Compares the predictions of `booster` to the labels of the given dataset.
+    pub fn evaluate(
+        booster: &Booster,
+        DataSet(features, labels): &DataSet,
+    ) -> anyhow::Result<Self> {
+        if labels.is_empty() {
+            return Err(anyhow!("can't evaluate a model on an empty dataset"));
+        }
+
+        let predictions = booster.predict(features.clone())?;
+        let (squared_error, absolute_error) = predictions.iter().zip(labels).fold(
+            (0.0, 0.0),
+            |(squared_error, absolute_error), (prediction, label)| {
+                let error = prediction[0] - *label as f64;
+                (squared_error + error * error, absolute_error + error.abs())
+            },
+        );
+
+        Ok(Self {
+            samples: labels.len(),
+            rmse: (squared_error / labels.len() as f64).sqrt(),
+            mae: absolute_error / labels.len() as f64,
+        })
+    }
+}
+
+/// Everything needed to trace a saved booster back to how it was trained.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelMetadata {
+    /// Unique name of the model, also used as its file name.
+    pub version: String,
+    pub symbol: String,
+    /// Unix timestamp in milliseconds.
+    pub created_at: u64,
+    /// Open time of the first candle the model was trained on.
+    pub window_start: u64,
+    /// Close time of the last candle the model was trained on.
+    pub window_end: u64,
+    /// LightGBM parameters the model was trained with.
+    pub params: Value,
+    /// Names of the features, in the order the model expects them.
+    pub features: Vec<String>,
+    pub label: Label,
+    pub metrics: ModelMetrics,
+}
+
+impl ModelMetadata {
+    /// Returns the version of a model of `symbol` created at the given time.
+    pub fn version(symbol: &str, created_at: u64) -> String {
+        format!("{}-{}", symbol.to_lowercase(), created_at)
+    }
+
+    /// The features the model expects, to compute them the same way it was trained.
+    pub fn pipeline(&self) -> anyhow::Result<FeaturePipeline> {
+        FeaturePipeline::try_from(self.features.clone())
+    }
+}
+
+/// A booster together with its metadata.
+pub struct TrainedModel {
+    pub booster: Booster,
+    pub metadata: ModelMetadata,
+}
+
+impl TrainedModel {
+    /// Trains a model on the given candles, oldest first, and records how it was trained.
+    pub fn train(
+        model: &Model,
+        symbol: &str,
+        dataset_config: &DataSetConfig,
+        candles: &[Candle],
+    ) -> anyhow::Result<Self> {
+        let (first, last) = match (candles.first(), candles.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(anyhow!("can't train a model without candles")),
+        };
+
+        let dataset = DataSet::new(candles, &dataset_config.label, &dataset_config.features);
+        info!(
+            "Last features, label in dataset: {:?}, {:?}",
+            dataset.0.last(),
+            dataset.1.last()
+        );
+
+        info!("Training model");
+        let start = Instant::now();
+        let booster = model.train(dataset.clone())?;
+        info!(
+            "Model trained successfully! Time elapsed: {:?}",
+            start.elapsed()
+        );
+        let metrics = ModelMetrics::evaluate(&booster, &dataset)?;
+
+        let created_at = now().as_millis() as u64;
+        Ok(Self {
+            booster,
+            metadata: ModelMetadata {
+                version: ModelMetadata::version(symbol, created_at),
+                symbol: symbol.to_string(),
+                created_at,
+                window_start: first.open_time,
+                window_end: last.close_time,
+                params: model.params().clone(),
+                features: dataset_config.features.names(),
+                label: dataset_config.label,
+                metrics,
+            },
+        })
+    }
+}
+
+/// Saves trained boosters to a directory, next to a JSON file with their metadata.
+pub struct ModelStore {
+    dir: PathBuf,
+}
+
+impl ModelStore {
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    fn model_path(&self, version: &str) -> PathBuf {
+        self.dir.join(format!("{}.txt", version))
+    }
+
+    fn metadata_path(&self, version: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", version))
+    }
+
+    /// Saves the booster and its metadata.
+    pub fn save(&self, model: &TrainedModel) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let version = &model.metadata.version;
+        model
+            .booster
+            .save_file(path_str(&self.model_path(version))?)?;
+        // The metadata is written last, so a model without metadata is never picked up as the latest.
+        fs::write(
+            self.metadata_path(version),
+            serde_json::to_string_pretty(&model.metadata)?,
+        )?;
+
+        Ok(())
+    }
+
+    /// Loads the booster and metadata of the given version.
+    pub fn load(&self, version: &str) -> anyhow::Result<TrainedModel> {
+        let metadata = serde_json::from_str(&fs::read_to_string(self.metadata_path(version))?)?;
+        let booster = Booster::from_file(path_str(&self.model_path(version))?)?;
+
+        Ok(TrainedModel { booster, metadata })
+    }
+
+    /// Returns the metadata of all saved models of `symbol`, oldest first.
+    pub fn list(&self, symbol: &str) -> anyhow::Result<Vec<ModelMetadata>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut models = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+                continue;
+            }
+
+            // Anything else in the directory shouldn't keep the saved models from being used.
+            let metadata = match fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| Ok(serde_json::from_str::<ModelMetadata>(&json)?))
+            {
+                Ok(metadata) => metadata,
+                Err(err) => {
+                    warn!(
+                        "Skipping {}, which isn't model metadata: {:#}",
+                        path.display(),
+                        err
+                    );
+                    continue;
+                }
+            };
+            if metadata.symbol == symbol {
+                models.push(metadata);
+            }
+        }
+        models.sort_by_key(|metadata| metadata.created_at);
+
+        Ok(models)
+    }
+
+    /// Loads the most recently created model of `symbol`, if any.
+    pub fn latest(&self, symbol: &str) -> anyhow::Result<Option<TrainedModel>> {
+        match self.list(symbol)?.last() {
+            Some(metadata) => Ok(Some(self.load(&metadata.version)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn path_str(path: &Path) -> anyhow::Result<&str> {
+    path.to_str()
+        .ok_or(anyhow!("invalid path {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use serde_json::json;
+
+    use crate::{
+        dataset::{DataSet, Label},
+        model::Model,
+    };
+
+    use super::{ModelMetadata, ModelMetrics, ModelStore, TrainedModel};
+
+    fn metadata(created_at: u64) -> ModelMetadata {
+        ModelMetadata {
+            version: ModelMetadata::version("BTCUSDT", created_at),
+            symbol: String::from("BTCUSDT"),
+            created_at,
+            window_start: 0,
+            window_end: created_at - 1,
+            params: json!({ "num_leaves": 13 }),
+            features: vec![String::from("open")],
+            label: Label::MaxHigh { horizon: 1 },
+            metrics: ModelMetrics {
+                samples: 10,
+                rmse: 1.0,
+                mae: 0.5,
+            },
+        }
+    }
+
+    #[test]
+    fn test_store() {
+        let dir = env::temp_dir().join("ml-crypto-trading-bot-test-models");
+        let _ = fs::remove_dir_all(&dir);
+        let store = ModelStore::new(dir.to_str().unwrap());
+        assert!(store.latest("BTCUSDT").unwrap().is_none());
+
+        let dataset = DataSet(vec![vec![1.0], vec![2.0], vec![3.0]], vec![2.0, 3.0, 4.0]);
+        for created_at in [2000, 1000] {
+            let model = TrainedModel {
+                booster: Model::new().train(dataset.clone()).unwrap(),
+                metadata: metadata(created_at),
+            };
+            store.save(&model).unwrap();
+        }
+
+        let versions: Vec<String> = store
+            .list("BTCUSDT")
+            .unwrap()
+            .into_iter()
+            .map(|metadata| metadata.version)
+            .collect();
+        assert_eq!(versions, vec!["btcusdt-1000", "btcusdt-2000"]);
+        assert!(store.list("ETHUSDT").unwrap().is_empty());
+        fs::write(dir.join("notes.json"), "not a model").unwrap();
+        assert_eq!(store.list("BTCUSDT").unwrap().len(), 2);
+        let latest = store.latest("BTCUSDT").unwrap().unwrap();
+        assert_eq!(latest.metadata, metadata(2000));
+        assert!(store.load("btcusdt-3000").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,78 @@
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Amount of notifications buffered per receiver.
+/// A receiver that falls further behind misses the oldest ones, the strategy is never held up.
+pub const NOTIFICATION_BUFFER: usize = 64;
+
+/// Sending half of the notification channel, used by the strategy thread.
+/// Sending never blocks, so it can be used outside of the tokio runtime.
+#[derive(Clone)]
+pub struct NotificationSender<T> {
+    tx: broadcast::Sender<T>,
+}
+
+/// Receiving half of the notification channel, every receiver gets every notification.
+pub struct NotificationReceiver<T> {
+    rx: broadcast::Receiver<T>,
+}
+
+/// Creates a notification channel that buffers up to `capacity` notifications per receiver.
+/// The channel closes once every sender has been dropped and the receivers have read what's left.
+pub fn channel<T: Clone>(capacity: usize) -> (NotificationSender<T>, NotificationReceiver<T>) {
+    let (tx, rx) = broadcast::channel(capacity);
+    (NotificationSender { tx }, NotificationReceiver { rx })
+}
+
+impl<T: Clone> NotificationSender<T> {
+    pub fn send(&self, notification: T) {
+        // Only fails when nobody is listening, which is fine.
+        if self.tx.send(notification).is_err() {
+            debug!("Dropped a notification without receivers.");
+        }
+    }
+
+    /// Adds a receiver that gets every notification sent from now on.
+    pub fn subscribe(&self) -> NotificationReceiver<T> {
+        NotificationReceiver {
+            rx: self.tx.subscribe(),
+        }
+    }
+}
+
+impl<T: Clone> NotificationReceiver<T> {
+    /// Waits for the next notification.
+    /// Returns `None` once the channel is closed.
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            match self.rx.recv().await {
+                Ok(notification) => return Some(notification),
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("Missed {} notifications, receiver is too slow.", missed)
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::channel;
+
+    #[tokio::test]
+    async fn test_channel() {
+        let (tx, mut rx) = channel::<String>(2);
+        let mut other_rx = tx.subscribe();
+
+        tx.send(String::from("a"));
+        tx.send(String::from("b"));
+        tx.send(String::from("c"));
+        drop(tx);
+
+        // A slow receiver misses the oldest notifications, the rest still arrive before the channel closes.
+        assert_eq!(rx.recv().await.as_deref(), Some("b"));
+        assert_eq!(rx.recv().await.as_deref(), Some("c"));
+        assert_eq!(rx.recv().await, None);
+        assert_eq!(other_rx.recv().await.as_deref(), Some("b"));
+    }
+}
@@ -0,0 +1,501 @@
+use std::{fs::OpenOptions, io::Write, time::Duration};
+
+use anyhow::anyhow;
+
+use async_trait::async_trait;
+use lettre::{
+    message::header::ContentType, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    config::{NotifierConfig, NotifierKind, SmtpConfig, SmtpTls},
+    events::TradeEvent,
+    notifications::NotificationReceiver,
+    utils::{format_timestamp, now},
+};
+
+/// How long sending a notification may take, so a server that hangs can't keep the bot from exiting.
+const NOTIFICATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Somewhere trade events are sent to, so they can be followed without reading the logs.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Name used in the logs.
+    fn name(&self) -> &str;
+
+    async fn notify(&self, event: &TradeEvent) -> anyhow::Result<()>;
+}
+
+/// Sends the events to the notifier until the strategy stops, routine events only when asked to.
+/// A failure is logged and doesn't stop the next events from being sent.
+pub async fn run(
+    notifier: Box<dyn Notifier>,
+    routine: bool,
+    mut notifications: NotificationReceiver<TradeEvent>,
+) {
+    while let Some(event) = notifications.recv().await {
+        if event.is_routine() && !routine {
+            continue;
+        }
+        if let Err(err) = notifier.notify(&event).await {
+            warn!("Failed to send {} notification: {:#}", notifier.name(), err);
+        }
+    }
+}
+
+pub fn from_config(config: &NotifierConfig) -> Box<dyn Notifier> {
+    match &config.kind {
+        NotifierKind::Discord { webhook_url } => Box::new(DiscordNotifier::new(webhook_url)),
+        NotifierKind::Slack { webhook_url } => Box::new(SlackNotifier::new(webhook_url)),
+        NotifierKind::Webhook { url } => Box::new(WebhookNotifier::new(url)),
+        NotifierKind::Smtp(smtp) => Box::new(SmtpNotifier::new(smtp.clone())),
+        NotifierKind::Stdout => Box::new(StdoutNotifier),
+        NotifierKind::File { path } => Box::new(FileNotifier::new(path)),
+    }
+}
+
+/// The event as it's posted to webhooks and written to files.
+#[derive(Serialize)]
+struct EventPayload<'a> {
+    message: String,
+    timestamp: String,
+    event: &'a TradeEvent,
+}
+
+impl<'a> EventPayload<'a> {
+    fn new(event: &'a TradeEvent) -> Self {
+        Self {
+            message: event.to_string(),
+            timestamp: format_timestamp(now()),
+            event,
+        }
+    }
+}
+
+fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(NOTIFICATION_TIMEOUT)
+        .build()
+        .expect("failed to create the HTTP client")
+}
+
+/// Posts the body to the URL as JSON, any status other than a success is an error.
+async fn post_json<T: Serialize + ?Sized>(
+    client: &reqwest::Client,
+    url: &str,
+    body: &T,
+) -> anyhow::Result<()> {
+    client
+        .post(url)
+        .json(body)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Posts the messages to a discord channel through a webhook.
+pub struct DiscordNotifier {
+    client: reqwest::Client,
+    webhook_url: String,
+}
+
+impl DiscordNotifier {
+    pub fn new(webhook_url: &str) -> Self {
+        Self {
+            client: http_client(),
+            webhook_url: webhook_url.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for DiscordNotifier {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    async fn notify(&self, event: &TradeEvent) -> anyhow::Result<()> {
+        let body = json!({ "content": event.to_string() });
+        post_json(&self.client, &self.webhook_url, &body).await
+    }
+}
+
+/// Posts the messages to a slack channel through an incoming webhook.
+pub struct SlackNotifier {
+    client: reqwest::Client,
+    webhook_url: String,
+}
+
+impl SlackNotifier {
+    pub fn new(webhook_url: &str) -> Self {
+        Self {
+            client: http_client(),
+            webhook_url: webhook_url.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for SlackNotifier {
+    fn name(&self) -> &str {
+        "slack"
+    }
+
+    async fn notify(&self, event: &TradeEvent) -> anyhow::Result<()> {
+        let body = json!({ "text": event.to_string() });
+        post_json(&self.client, &self.webhook_url, &body).await
+    }
+}
+
+/// Posts the events as JSON, with their message and when they were sent.
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: http_client(),
+            url: url.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn notify(&self, event: &TradeEvent) -> anyhow::Result<()> {
+        post_json(&self.client, &self.url, &EventPayload::new(event)).await
+    }
+}
+
+/// Mails the messages, with the event details in the body.
+pub struct SmtpNotifier {
+    config: SmtpConfig,
+}
+
+impl SmtpNotifier {
+    pub fn new(config: SmtpConfig) -> Self {
+        Self { config }
+    }
+
+    /// Connects to the mail server for a single mail, there are too few of them to keep the connection open.
+    fn transport(&self) -> anyhow::Result<AsyncSmtpTransport<Tokio1Executor>> {
+        let host = &self.config.host;
+        let mut builder = match self.config.tls {
+            SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        }
+        .timeout(Some(NOTIFICATION_TIMEOUT));
+        if let Some(port) = self.config.port {
+            builder = builder.port(port);
+        }
+        if let (Some(username), Some(password)) = (&self.config.username, &self.config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(builder.build())
+    }
+
+    fn message(&self, subject: &str, body: String) -> anyhow::Result<Message> {
+        let mut builder = Message::builder()
+            .from(self.config.from.parse()?)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.config.to {
+            builder = builder.to(to.parse()?);
+        }
+
+        Ok(builder.body(body)?)
+    }
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    fn name(&self) -> &str {
+        "smtp"
+    }
+
+    async fn notify(&self, event: &TradeEvent) -> anyhow::Result<()> {
+        let message = event.to_string();
+        let body = format!("{}\n\n{}", message, serde_json::to_string_pretty(event)?);
+        let mail = self.message(&message, body)?;
+
+        tokio::time::timeout(NOTIFICATION_TIMEOUT, self.transport()?.send(mail))
+            .await
+            .map_err(|_| anyhow!("timed out after {:?}", NOTIFICATION_TIMEOUT))??;
+        Ok(())
+    }
+}
+
+/// Prints the messages, for when the bot runs in a terminal or under a process manager that keeps its output.
+pub struct StdoutNotifier;
+
+#[async_trait]
+impl Notifier for StdoutNotifier {
+    fn name(&self) -> &str {
+        "stdout"
+    }
+
+    async fn notify(&self, event: &TradeEvent) -> anyhow::Result<()> {
+        println!("[{}] {}", format_timestamp(now()), event);
+        Ok(())
+    }
+}
+
+/// Appends the events to a file, one JSON object per line.
+pub struct FileNotifier {
+    path: String,
+}
+
+impl FileNotifier {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for FileNotifier {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    async fn notify(&self, event: &TradeEvent) -> anyhow::Result<()> {
+        let mut line = serde_json::to_string(&EventPayload::new(event))?;
+        line.push('\n');
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::mpsc::{channel, Receiver},
+        thread,
+    };
+
+    use serde_json::Value;
+
+    use crate::{
+        config::{NotifierConfig, NotifierKind, SmtpConfig, SmtpTls},
+        events::{SkipReason, TradeEvent},
+        notifications,
+    };
+
+    use super::{from_config, run, Notifier, SmtpNotifier};
+
+    /// Answers requests on a local port with the given status, returns the URL and the bodies it receives.
+    fn http_stub(status: &'static str) -> (String, Receiver<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (tx, rx) = channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+
+                let mut stream = stream;
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+                tx.send(serde_json::from_slice(&body).unwrap()).unwrap();
+            }
+        });
+
+        (url, rx)
+    }
+
+    /// Accepts a single mail on a local port, returns the port and the commands and data it receives.
+    fn smtp_stub() -> (u16, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = channel();
+
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut received = String::new();
+            let mut data = false;
+
+            writer.write_all(b"220 stub ready\r\n").unwrap();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                received.push_str(&line);
+
+                let reply: &[u8] = if data {
+                    if line != ".\r\n" {
+                        continue;
+                    }
+                    data = false;
+                    b"250 queued\r\n"
+                } else if line.starts_with("EHLO") {
+                    b"250-stub\r\n250 AUTH PLAIN\r\n"
+                } else if line.starts_with("AUTH") {
+                    b"235 authenticated\r\n"
+                } else if line.starts_with("DATA") {
+                    data = true;
+                    b"354 go ahead\r\n"
+                } else if line.starts_with("QUIT") {
+                    writer.write_all(b"221 bye\r\n").unwrap();
+                    break;
+                } else {
+                    b"250 ok\r\n"
+                };
+                writer.write_all(reply).unwrap();
+            }
+            tx.send(received).unwrap();
+        });
+
+        (port, rx)
+    }
+
+    fn notifier(kind: NotifierKind) -> Box<dyn Notifier> {
+        from_config(&NotifierConfig {
+            kind,
+            routine: false,
+        })
+    }
+
+    fn failed() -> TradeEvent {
+        TradeEvent::Error {
+            message: String::from("Failed to sell BTCUSDT."),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_webhooks() {
+        let (url, rx) = http_stub("204 No Content");
+        let discord = notifier(NotifierKind::Discord { webhook_url: url });
+        discord.notify(&failed()).await.unwrap();
+        assert_eq!(
+            rx.recv().unwrap(),
+            serde_json::json!({ "content": "Failed to sell BTCUSDT." })
+        );
+
+        let (url, rx) = http_stub("200 OK");
+        let slack = notifier(NotifierKind::Slack { webhook_url: url });
+        slack.notify(&failed()).await.unwrap();
+        assert_eq!(
+            rx.recv().unwrap(),
+            serde_json::json!({ "text": "Failed to sell BTCUSDT." })
+        );
+
+        let (url, rx) = http_stub("200 OK");
+        let webhook = notifier(NotifierKind::Webhook { url });
+        webhook.notify(&failed()).await.unwrap();
+        let body = rx.recv().unwrap();
+        assert_eq!(body["message"], "Failed to sell BTCUSDT.");
+        assert_eq!(
+            body["event"],
+            serde_json::json!({ "event": "error", "message": "Failed to sell BTCUSDT." })
+        );
+        assert!(body["timestamp"].is_string());
+
+        let (url, _rx) = http_stub("404 Not Found");
+        let webhook = notifier(NotifierKind::Webhook { url });
+        assert!(webhook.notify(&failed()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_smtp() {
+        let (port, rx) = smtp_stub();
+        SmtpNotifier::new(SmtpConfig {
+            host: String::from("127.0.0.1"),
+            port: Some(port),
+            tls: SmtpTls::None,
+            username: Some(String::from("bot")),
+            password: Some(String::from("secret")),
+            from: String::from("bot@example.com"),
+            to: vec![
+                String::from("alice@example.com"),
+                String::from("bob@example.com"),
+            ],
+        })
+        .notify(&TradeEvent::Shutdown)
+        .await
+        .unwrap();
+
+        let received = rx.recv().unwrap();
+        assert!(received.contains("AUTH PLAIN AGJvdABzZWNyZXQ=\r\n"));
+        assert!(received.contains("RCPT TO:<alice@example.com>\r\n"));
+        assert!(received.contains("RCPT TO:<bob@example.com>\r\n"));
+        assert!(received.contains("Subject: Trading stopped.\r\n"));
+        assert!(received.contains("\"event\": \"shutdown\""));
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let path = env::temp_dir().join(format!("notifier-test-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let file = notifier(NotifierKind::File {
+            path: path.to_string_lossy().to_string(),
+        });
+
+        let (tx, rx) = notifications::channel(8);
+        tx.send(TradeEvent::TradeSkipped {
+            symbol: String::from("BTCUSDT"),
+            reason: SkipReason::PredictionBelowPrice {
+                predicted: 19000.0,
+                open: 20000.0,
+                close: 20100.0,
+            },
+        });
+        tx.send(failed());
+        tx.send(TradeEvent::Shutdown);
+        drop(tx);
+        run(file, false, rx).await;
+
+        // The routine event is skipped.
+        let events = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap()["event"]["event"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(events, vec!["error", "shutdown"]);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,344 @@
+use std::{fmt, fs, io::ErrorKind, path::PathBuf};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::config::TradeConfig;
+
+/// Lifecycle of a single trade: `Flat` → `Buying` → `Holding` → `Selling` → `Flat`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum Position {
+    /// No open position.
+    Flat,
+
+    /// A buy order for `amount` of the quote asset is being placed.
+    Buying { amount: f64, target: f64 },
+
+    /// `quantity` of the base asset was bought at `price` and will be sold once `target` is reached.
+    Holding {
+        quantity: f64,
+        price: f64,
+        target: f64,
+        /// Unix timestamp in milliseconds.
+        opened_at: u64,
+        /// Highest price seen since buying, used by the trailing stop.
+        #[serde(default)]
+        highest_price: f64,
+        /// ID of the limit sell order resting at `target`, if one was placed.
+        #[serde(default)]
+        take_profit_order: Option<u64>,
+    },
+
+    /// A sell order for the held `quantity` is being placed.
+    Selling {
+        quantity: f64,
+        price: f64,
+        target: f64,
+        opened_at: u64,
+        #[serde(default)]
+        highest_price: f64,
+        #[serde(default)]
+        take_profit_order: Option<u64>,
+        reason: ExitReason,
+    },
+}
+
+impl Position {
+    /// Returns whether moving from this state to `next` is a valid transition.
+    pub fn can_transition(&self, next: &Position) -> bool {
+        matches!(
+            (self, next),
+            (Position::Flat, Position::Buying { .. })
+                | (Position::Buying { .. }, Position::Holding { .. })
+                // The buy order failed.
+                | (Position::Buying { .. }, Position::Flat)
+                // A new highest price was seen.
+                | (Position::Holding { .. }, Position::Holding { .. })
+                | (Position::Holding { .. }, Position::Selling { .. })
+                | (Position::Selling { .. }, Position::Flat)
+                // The sell order failed.
+                | (Position::Selling { .. }, Position::Holding { .. })
+        )
+    }
+}
+
+/// Why an open position was sold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitReason {
+    /// The predicted price was reached.
+    Target,
+    StopLoss,
+    TrailingStop,
+    MaxHoldingTime,
+    /// Selling was requested over telegram.
+    Manual,
+}
+
+impl fmt::Display for ExitReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ExitReason::Target => "target reached",
+            ExitReason::StopLoss => "stop-loss",
+            ExitReason::TrailingStop => "trailing stop",
+            ExitReason::MaxHoldingTime => "maximum holding time",
+            ExitReason::Manual => "sold on request",
+        })
+    }
+}
+
+/// Exits besides the predicted target, configured in `TradeConfig`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExitRules {
+    /// Sell when the price drops this many percent below the buying price.
+    pub stop_loss: Option<f64>,
+    /// Sell when the price drops this many percent below the highest price since buying.
+    pub trailing_stop: Option<f64>,
+    /// Sell once the position has been held for this many candles.
+    pub max_holding_candles: Option<u64>,
+}
+
+impl ExitRules {
+    pub fn from_config(config: &TradeConfig) -> Self {
+        Self {
+            stop_loss: config.stop_loss,
+            trailing_stop: config.trailing_stop,
+            max_holding_candles: config.max_holding_candles,
+        }
+    }
+
+    fn stop_loss_price(&self, price: f64) -> Option<f64> {
+        self.stop_loss
+            .map(|percentage| price * (1.0 - percentage / 100.0))
+    }
+
+    fn trailing_stop_price(&self, price: f64, highest_price: f64) -> Option<f64> {
+        self.trailing_stop
+            .map(|percentage| highest_price.max(price) * (1.0 - percentage / 100.0))
+    }
+
+    /// Returns the highest price at which one of the stops is triggered, if any are configured.
+    pub fn stop_price(&self, price: f64, highest_price: f64) -> Option<f64> {
+        match (
+            self.stop_loss_price(price),
+            self.trailing_stop_price(price, highest_price),
+        ) {
+            (Some(stop_loss), Some(trailing_stop)) => Some(stop_loss.max(trailing_stop)),
+            (stop_loss, trailing_stop) => stop_loss.or(trailing_stop),
+        }
+    }
+
+    /// Returns why a position bought at `price` should be sold at `current_price`, if it should be sold at all.
+    pub fn check(
+        &self,
+        price: f64,
+        target: f64,
+        highest_price: f64,
+        current_price: f64,
+        candles_held: u64,
+    ) -> Option<ExitReason> {
+        if current_price >= target {
+            return Some(ExitReason::Target);
+        }
+
+        if matches!(self.stop_loss_price(price), Some(stop) if current_price <= stop) {
+            return Some(ExitReason::StopLoss);
+        }
+
+        if matches!(self.trailing_stop_price(price, highest_price), Some(stop) if current_price <= stop)
+        {
+            return Some(ExitReason::TrailingStop);
+        }
+
+        match self.max_holding_candles {
+            Some(max) if candles_held >= max => Some(ExitReason::MaxHoldingTime),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PositionFile {
+    symbol: String,
+    position: Position,
+}
+
+/// Keeps track of the current position and persists it to a JSON file after every transition.
+pub struct PositionStore {
+    path: PathBuf,
+    symbol: String,
+    position: Position,
+}
+
+impl PositionStore {
+    /// Loads the position of `symbol` from the given file.
+    /// A missing file means there is no open position.
+    pub fn open(path: &str, symbol: &str) -> anyhow::Result<Self> {
+        let position = match fs::read_to_string(path) {
+            Ok(contents) => {
+                let file: PositionFile = serde_json::from_str(&contents)?;
+                if file.symbol != symbol {
+                    return Err(anyhow!(
+                        "{} contains a position for {} instead of {}",
+                        path,
+                        file.symbol,
+                        symbol
+                    ));
+                }
+                file.position
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => Position::Flat,
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self {
+            path: PathBuf::from(path),
+            symbol: symbol.to_string(),
+            position,
+        })
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    /// Moves to the next state and persists it.
+    pub fn transition(&mut self, next: Position) -> anyhow::Result<()> {
+        if !self.position.can_transition(&next) {
+            return Err(anyhow!(
+                "invalid position transition from {:?} to {:?}",
+                self.position,
+                next
+            ));
+        }
+
+        debug!(
+            "Position transition from {:?} to {:?}.",
+            self.position, next
+        );
+        self.position = next;
+        self.save()
+    }
+
+    /// Writes the current state to a temporary file first and then moves it in place,
+    /// so a crash during the write never leaves a corrupt file behind.
+    fn save(&self) -> anyhow::Result<()> {
+        let contents = serde_json::to_string_pretty(&PositionFile {
+            symbol: self.symbol.clone(),
+            position: self.position.clone(),
+        })?;
+
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, contents)?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::{ExitReason, ExitRules, Position, PositionStore};
+
+    #[test]
+    fn test_can_transition() {
+        let buying = Position::Buying {
+            amount: 50.0,
+            target: 2.0,
+        };
+        let holding = Position::Holding {
+            quantity: 1.0,
+            price: 1.0,
+            target: 2.0,
+            opened_at: 0,
+            highest_price: 1.0,
+            take_profit_order: None,
+        };
+        let selling = Position::Selling {
+            quantity: 1.0,
+            price: 1.0,
+            target: 2.0,
+            opened_at: 0,
+            highest_price: 1.0,
+            take_profit_order: None,
+            reason: ExitReason::Target,
+        };
+
+        assert!(Position::Flat.can_transition(&buying));
+        assert!(buying.can_transition(&holding));
+        assert!(holding.can_transition(&selling));
+        assert!(selling.can_transition(&Position::Flat));
+        assert!(!Position::Flat.can_transition(&holding));
+        assert!(!holding.can_transition(&Position::Flat));
+        assert!(!holding.can_transition(&buying));
+    }
+
+    #[test]
+    fn test_exit_rules() {
+        let rules = ExitRules::default();
+        assert_eq!(rules.check(100.0, 110.0, 100.0, 50.0, 1000), None);
+        assert_eq!(
+            rules.check(100.0, 110.0, 100.0, 110.0, 0),
+            Some(ExitReason::Target)
+        );
+        assert_eq!(rules.stop_price(100.0, 100.0), None);
+
+        let rules = ExitRules {
+            stop_loss: Some(5.0),
+            trailing_stop: Some(10.0),
+            max_holding_candles: Some(24),
+        };
+        assert_eq!(rules.check(100.0, 110.0, 100.0, 96.0, 0), None);
+        assert_eq!(
+            rules.check(100.0, 110.0, 100.0, 95.0, 0),
+            Some(ExitReason::StopLoss)
+        );
+        assert_eq!(
+            rules.check(100.0, 120.0, 115.0, 103.0, 0),
+            Some(ExitReason::TrailingStop)
+        );
+        assert_eq!(
+            rules.check(100.0, 110.0, 100.0, 100.0, 24),
+            Some(ExitReason::MaxHoldingTime)
+        );
+        assert_eq!(rules.stop_price(100.0, 100.0), Some(95.0));
+        assert_eq!(rules.stop_price(100.0, 120.0), Some(108.0));
+    }
+
+    #[test]
+    fn test_persistence() {
+        let path = env::temp_dir().join("ml-crypto-trading-bot-test-position.json");
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+
+        let mut store = PositionStore::open(path, "BTCUSDT").unwrap();
+        assert_eq!(store.position(), &Position::Flat);
+
+        let buying = Position::Buying {
+            amount: 50.0,
+            target: 2.0,
+        };
+        store.transition(buying.clone()).unwrap();
+        assert!(store.transition(Position::Flat).is_ok());
+        store.transition(buying).unwrap();
+        let holding = Position::Holding {
+            quantity: 1.0,
+            price: 1.0,
+            target: 2.0,
+            opened_at: 1674925200000,
+            highest_price: 1.5,
+            take_profit_order: Some(42),
+        };
+        store.transition(holding.clone()).unwrap();
+        assert!(store.transition(Position::Flat).is_err());
+
+        let store = PositionStore::open(path, "BTCUSDT").unwrap();
+        assert_eq!(store.position(), &holding);
+        assert!(PositionStore::open(path, "ETHUSDT").is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+}
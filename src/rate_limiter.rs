@@ -0,0 +1,253 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+
+use crate::{shutdown::Shutdown, utils::now};
+
+/// Source of time for the rate limiter, so it can be tested without waiting.
+pub trait Clock: Send + Sync {
+    /// Returns the current time since the unix epoch.
+    fn now(&self) -> Duration;
+
+    /// Returns `false` when the sleep was interrupted.
+    fn sleep(&self, duration: Duration) -> bool;
+}
+
+/// The real clock, its sleeps are cut short when a shutdown is requested.
+pub struct SystemClock(pub Arc<Shutdown>);
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        now()
+    }
+
+    fn sleep(&self, duration: Duration) -> bool {
+        self.0.sleep(duration)
+    }
+}
+
+/// Binance endpoints the bot calls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endpoint {
+    /// `GET /api/v3/klines` with the given limit.
+    Klines { limit: u16 },
+    /// `GET /api/v3/ticker/price` for a single symbol.
+    Price,
+    /// `POST /api/v3/order`, `POST /api/v3/order/test`, `POST /api/v3/order/oco` and `DELETE /api/v3/order`.
+    Order,
+    /// `GET /api/v3/order`.
+    OrderStatus,
+    /// `GET /api/v3/openOrders` for a single symbol.
+    OpenOrders,
+    /// `GET /api/v3/exchangeInfo`.
+    ExchangeInfo,
+    /// `GET /api/v3/account`.
+    Account,
+}
+
+impl Endpoint {
+    /// Returns the request weight of the endpoint.
+    /// See https://binance-docs.github.io/apidocs/spot/en/#market-data-endpoints.
+    pub fn weight(&self) -> u32 {
+        match self {
+            Endpoint::Klines { limit } => match limit {
+                0..=99 => 1,
+                100..=499 => 2,
+                500..=1000 => 5,
+                _ => 10,
+            },
+            Endpoint::Price => 2,
+            Endpoint::Order => 1,
+            Endpoint::OrderStatus => 4,
+            Endpoint::OpenOrders => 6,
+            Endpoint::ExchangeInfo => 20,
+            Endpoint::Account => 20,
+        }
+    }
+}
+
+/// Binance counts the used request weight per minute, starting at every full minute.
+const WINDOW: Duration = Duration::from_secs(60);
+
+struct Window {
+    /// Number of the current minute since the unix epoch.
+    minute: u64,
+    used: u32,
+}
+
+/// Keeps the request weight used per minute below a limit, by waiting for the next minute when a request would exceed it.
+/// The weight is only counted on our side: binance-rs doesn't expose the `X-MBX-USED-WEIGHT-1M` response header,
+/// so the weight binance actually counted is never read. Only the requests made through the limiter are counted,
+/// so the limit should leave room for other clients with the same IP.
+pub struct RateLimiter {
+    limit: u32,
+    clock: Box<dyn Clock>,
+    window: Mutex<Window>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32, clock: Box<dyn Clock>) -> Self {
+        Self {
+            limit,
+            clock,
+            window: Mutex::new(Window { minute: 0, used: 0 }),
+        }
+    }
+
+    /// Returns the weight used in the current minute.
+    pub fn used_weight(&self) -> u32 {
+        let mut window = self.window.lock().unwrap();
+        advance(&mut window, self.clock.now());
+        window.used
+    }
+
+    /// Waits until a request to `endpoint` fits in the weight limit and counts its weight.
+    /// Fails when the wait was interrupted.
+    pub fn acquire(&self, endpoint: Endpoint) -> anyhow::Result<()> {
+        // A single request heavier than the limit can never fit, so let it through at the start of a minute.
+        let weight = endpoint.weight().min(self.limit);
+
+        loop {
+            let wait = {
+                let now = self.clock.now();
+                let mut window = self.window.lock().unwrap();
+                advance(&mut window, now);
+
+                if window.used + weight <= self.limit {
+                    window.used += weight;
+                    return Ok(());
+                }
+
+                Duration::from_secs((window.minute + 1) * WINDOW.as_secs()).saturating_sub(now)
+            };
+
+            debug!(
+                "Request weight limit of {} reached, waiting {:?} before calling {:?}.",
+                self.limit, wait, endpoint
+            );
+            if !self.clock.sleep(wait) {
+                return Err(anyhow!(
+                    "interrupted while waiting for the request weight limit"
+                ));
+            }
+        }
+    }
+}
+
+/// Starts counting from zero when a new minute started.
+fn advance(window: &mut Window, now: Duration) {
+    let minute = now.as_secs() / WINDOW.as_secs();
+    if minute != window.minute {
+        window.minute = minute;
+        window.used = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::{Clock, Endpoint, RateLimiter};
+
+    /// A clock that only moves when sleeping.
+    #[derive(Clone)]
+    struct FakeClock(Arc<Mutex<Duration>>);
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Duration {
+            *self.0.lock().unwrap()
+        }
+
+        fn sleep(&self, duration: Duration) -> bool {
+            *self.0.lock().unwrap() += duration;
+            true
+        }
+    }
+
+    #[test]
+    fn test_weight() {
+        assert_eq!(Endpoint::Klines { limit: 1 }.weight(), 1);
+        assert_eq!(Endpoint::Klines { limit: 500 }.weight(), 5);
+        assert_eq!(Endpoint::Klines { limit: 1500 }.weight(), 10);
+        assert_eq!(Endpoint::Price.weight(), 2);
+    }
+
+    #[test]
+    fn test_acquire() {
+        let start = Duration::from_millis(1674925230000); // 2023-01-28T17:00:30Z
+        let clock = FakeClock(Arc::new(Mutex::new(start)));
+        let limiter = RateLimiter::new(25, Box::new(clock.clone()));
+
+        // Two requests of weight 10 fit in the first minute.
+        limiter.acquire(Endpoint::Klines { limit: 1500 }).unwrap();
+        limiter.acquire(Endpoint::Klines { limit: 1500 }).unwrap();
+        assert_eq!(clock.now(), start);
+        assert_eq!(limiter.used_weight(), 20);
+
+        // The third one has to wait for the next minute.
+        limiter.acquire(Endpoint::Klines { limit: 1500 }).unwrap();
+        assert_eq!(clock.now(), start + Duration::from_secs(30));
+        assert_eq!(limiter.used_weight(), 10);
+
+        limiter.acquire(Endpoint::Klines { limit: 1500 }).unwrap();
+        limiter.acquire(Endpoint::Price).unwrap();
+        assert_eq!(clock.now(), start + Duration::from_secs(30));
+        limiter.acquire(Endpoint::Klines { limit: 1500 }).unwrap();
+        assert_eq!(clock.now(), start + Duration::from_secs(90));
+        assert_eq!(limiter.used_weight(), 10);
+    }
+
+    /// A clock that moves a second every time it's read.
+    struct TickingClock(Mutex<Duration>);
+
+    impl Clock for TickingClock {
+        fn now(&self) -> Duration {
+            let mut now = self.0.lock().unwrap();
+            *now += Duration::from_secs(1);
+            *now
+        }
+
+        fn sleep(&self, duration: Duration) -> bool {
+            *self.0.lock().unwrap() += duration;
+            true
+        }
+    }
+
+    #[test]
+    fn test_acquire_at_end_of_minute() {
+        // The minute is over right after the limit was found to be reached.
+        let start = Duration::from_millis(1674925257500); // 2023-01-28T17:00:57.5Z
+        let limiter = RateLimiter::new(10, Box::new(TickingClock(Mutex::new(start))));
+        limiter.acquire(Endpoint::Klines { limit: 1500 }).unwrap();
+        limiter.acquire(Endpoint::Klines { limit: 1500 }).unwrap();
+        limiter.acquire(Endpoint::Klines { limit: 1500 }).unwrap();
+        assert_eq!(limiter.used_weight(), 10);
+    }
+
+    /// A clock that's always interrupted when sleeping.
+    struct StoppedClock;
+
+    impl Clock for StoppedClock {
+        fn now(&self) -> Duration {
+            Duration::from_millis(1674925230000)
+        }
+
+        fn sleep(&self, _: Duration) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_acquire_interrupted() {
+        let limiter = RateLimiter::new(10, Box::new(StoppedClock));
+        limiter.acquire(Endpoint::Klines { limit: 1500 }).unwrap();
+        assert!(limiter.acquire(Endpoint::Price).is_err());
+        assert_eq!(limiter.used_weight(), 10);
+    }
+}
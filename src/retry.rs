@@ -0,0 +1,285 @@
+use std::time::Duration;
+
+use binance::errors::{Error, ErrorKind};
+use serde::Deserialize;
+
+use crate::shutdown::Shutdown;
+
+/// How failed binance requests are retried.
+/// Only used for requests that are safe to repeat, orders are never retried.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Amount of retries before giving up.
+    pub max_retries: u32,
+    /// Milliseconds to wait before the first retry.
+    pub initial_backoff: u64,
+    /// The wait is multiplied by this after every retry.
+    pub multiplier: f64,
+    /// Maximum amount of milliseconds to wait between retries.
+    pub max_backoff: u64,
+    /// Milliseconds to wait after binance responded that the request weight limit was exceeded.
+    /// binance-rs doesn't expose the `Retry-After` header, so this should be long enough for the limit to reset.
+    pub rate_limit_backoff: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: 500,
+            multiplier: 2.0,
+            max_backoff: 30_000,
+            rate_limit_backoff: 60_000,
+        }
+    }
+}
+
+/// What went wrong with a binance request, as far as retrying it is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// Network errors and server side failures that may succeed when tried again.
+    Transient,
+    /// Too many requests (HTTP 429), retrying is fine after waiting for the limit to reset.
+    RateLimited,
+    /// The IP got banned for ignoring rate limits (HTTP 418), retrying would only extend the ban.
+    Banned,
+    /// The request itself is invalid (e.g. an unknown symbol).
+    Fatal,
+}
+
+impl Failure {
+    pub fn classify(err: &Error) -> Self {
+        match err.kind() {
+            ErrorKind::BinanceError(content) => match content.code {
+                // TOO_MANY_REQUESTS
+                -1003 => Failure::RateLimited,
+                // UNKNOWN, DISCONNECTED, TIMEOUT and INVALID_TIMESTAMP (clock drift).
+                -1000 | -1001 | -1007 | -1021 => Failure::Transient,
+                _ => Failure::Fatal,
+            },
+            // binance-rs turns every unexpected status code into a message.
+            ErrorKind::Msg(msg) => match msg.as_str() {
+                "Received response: 429" => Failure::RateLimited,
+                "Received response: 418" => Failure::Banned,
+                "Internal Server Error" | "Service Unavailable" => Failure::Transient,
+                msg if msg.starts_with("Received response: 5") => Failure::Transient,
+                _ => Failure::Fatal,
+            },
+            // Connection and response body errors.
+            _ => Failure::Transient,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns how long to wait before the given retry, counting from 0.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self.initial_backoff as f64 * self.multiplier.powi(retry as i32);
+        Duration::from_millis(backoff.min(self.max_backoff as f64) as u64)
+    }
+
+    /// Calls `request` until it succeeds, fails for good, runs out of retries or a shutdown is requested.
+    pub fn retry<T, F>(
+        &self,
+        description: &str,
+        shutdown: &Shutdown,
+        request: F,
+    ) -> anyhow::Result<T>
+    where
+        F: FnMut() -> binance::errors::Result<T>,
+    {
+        self.retry_with(description, request, |wait| shutdown.sleep(wait))
+    }
+
+    /// Like `retry`, but waits with `sleep`, which returns `false` when it was interrupted.
+    pub fn retry_with<T, F, S>(
+        &self,
+        description: &str,
+        mut request: F,
+        mut sleep: S,
+    ) -> anyhow::Result<T>
+    where
+        F: FnMut() -> binance::errors::Result<T>,
+        S: FnMut(Duration) -> bool,
+    {
+        let mut retry = 0;
+
+        loop {
+            let err = match request() {
+                Ok(result) => return Ok(result),
+                Err(err) => err,
+            };
+
+            let wait = match Failure::classify(&err) {
+                Failure::Transient => self.backoff(retry),
+                Failure::RateLimited => {
+                    Duration::from_millis(self.rate_limit_backoff).max(self.backoff(retry))
+                }
+                Failure::Banned => {
+                    return Err(anyhow::anyhow!(
+                        "{} failed: binance banned this IP for exceeding rate limits ({})",
+                        description,
+                        err
+                    ))
+                }
+                Failure::Fatal => return Err(anyhow::anyhow!("{} failed: {}", description, err)),
+            };
+
+            if retry >= self.max_retries {
+                return Err(anyhow::anyhow!(
+                    "{} failed after {} retries: {}",
+                    description,
+                    retry,
+                    err
+                ));
+            }
+
+            warn!(
+                "{} failed: {}, retrying in {:?} ({}/{}).",
+                description,
+                err,
+                wait,
+                retry + 1,
+                self.max_retries
+            );
+            if !sleep(wait) {
+                return Err(anyhow::anyhow!(
+                    "{} failed: {}, interrupted before retrying",
+                    description,
+                    err
+                ));
+            }
+            retry += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use binance::errors::{BinanceContentError, Error, ErrorKind};
+
+    use super::{Failure, RetryPolicy};
+
+    fn binance_error(code: i16) -> Error {
+        Error::from(ErrorKind::BinanceError(BinanceContentError {
+            code,
+            msg: String::from("error"),
+        }))
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            Failure::classify(&Error::from("Received response: 429")),
+            Failure::RateLimited
+        );
+        assert_eq!(
+            Failure::classify(&Error::from("Received response: 418")),
+            Failure::Banned
+        );
+        assert_eq!(
+            Failure::classify(&Error::from("Service Unavailable")),
+            Failure::Transient
+        );
+        assert_eq!(
+            Failure::classify(&Error::from("Received response: 502")),
+            Failure::Transient
+        );
+        assert_eq!(
+            Failure::classify(&binance_error(-1003)),
+            Failure::RateLimited
+        );
+        assert_eq!(Failure::classify(&binance_error(-1121)), Failure::Fatal);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_millis(2000));
+        assert_eq!(policy.backoff(10), Duration::from_millis(30_000));
+    }
+
+    #[test]
+    fn test_retry() {
+        let policy = RetryPolicy::default();
+
+        // Transient failures are retried with exponential backoff, rate limits wait longer.
+        let mut responses = vec![
+            Err(Error::from("Internal Server Error")),
+            Err(Error::from("Received response: 429")),
+            Err(Error::from("Service Unavailable")),
+            Ok(42),
+        ]
+        .into_iter();
+        let mut waits = Vec::new();
+        let result = policy.retry_with(
+            "request",
+            || responses.next().unwrap(),
+            |wait| {
+                waits.push(wait);
+                true
+            },
+        );
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(
+            waits,
+            vec![
+                Duration::from_millis(500),
+                Duration::from_millis(60_000),
+                Duration::from_millis(2000)
+            ]
+        );
+
+        // Fatal errors and bans aren't retried.
+        let mut waits = Vec::new();
+        let result: anyhow::Result<()> = policy.retry_with(
+            "request",
+            || Err(binance_error(-1121)),
+            |wait| {
+                waits.push(wait);
+                true
+            },
+        );
+        assert!(result.is_err());
+        let result: anyhow::Result<()> = policy.retry_with(
+            "request",
+            || Err(Error::from("Received response: 418")),
+            |wait| {
+                waits.push(wait);
+                true
+            },
+        );
+        assert!(result.unwrap_err().to_string().contains("banned"));
+        assert!(waits.is_empty());
+
+        // Eventually it gives up.
+        let mut attempts = 0;
+        let result: anyhow::Result<()> = policy.retry_with(
+            "request",
+            || {
+                attempts += 1;
+                Err(Error::from("Service Unavailable"))
+            },
+            |_| true,
+        );
+        assert!(result.is_err());
+        assert_eq!(attempts, 6);
+
+        // A shutdown stops the retries.
+        let mut attempts = 0;
+        let result: anyhow::Result<()> = policy.retry_with(
+            "request",
+            || {
+                attempts += 1;
+                Err(Error::from("Service Unavailable"))
+            },
+            |_| false,
+        );
+        assert!(result.unwrap_err().to_string().contains("interrupted"));
+        assert_eq!(attempts, 1);
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
+
+use crate::{binance_market::BinanceKlineInterval, shutdown::Shutdown, utils::now};
+
+/// Keeps the strategy in step with the candles of the configured interval.
+/// All its waits are cut short when a shutdown is requested.
+pub struct Scheduler {
+    interval: BinanceKlineInterval,
+    shutdown: Arc<Shutdown>,
+}
+
+impl Scheduler {
+    pub fn new(interval: BinanceKlineInterval, shutdown: Arc<Shutdown>) -> Self {
+        Self { interval, shutdown }
+    }
+
+    /// Returns how long it takes from `now` until the next candle opens.
+    pub fn until_next_candle(&self, now: Duration) -> Duration {
+        self.interval.next(now) - now
+    }
+
+    /// Blocks until the next candle opens.
+    /// Returns `false` when a shutdown was requested in the meantime.
+    pub fn wait_for_next_candle(&self) -> bool {
+        self.shutdown.sleep(self.until_next_candle(now()))
+    }
+
+    /// Blocks for `duration`.
+    /// Returns `false` when a shutdown was requested in the meantime.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        self.shutdown.sleep(duration)
+    }
+
+    /// Returns a flag that's cleared as soon as a shutdown is requested, for loops that can't wait on anything else.
+    pub fn running_flag(&self) -> Arc<AtomicBool> {
+        self.shutdown.running_flag()
+    }
+
+    /// Requests everything to stop, e.g. when the strategy can't go on.
+    pub fn stop(&self) {
+        self.shutdown.trigger();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.shutdown.is_triggered()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{binance_market::BinanceKlineInterval, shutdown::Shutdown};
+
+    use super::Scheduler;
+
+    #[test]
+    fn test_until_next_candle() {
+        let timestamp = Duration::from_millis(1674940162060); // 2023-01-28T21:09:22.060Z
+
+        let scheduler = Scheduler::new(
+            BinanceKlineInterval::FifteenMinutes,
+            Arc::new(Shutdown::new()),
+        );
+        assert_eq!(
+            scheduler.until_next_candle(timestamp),
+            Duration::from_millis(337940)
+        );
+
+        let scheduler = Scheduler::new(BinanceKlineInterval::Daily, Arc::new(Shutdown::new()));
+        assert_eq!(
+            scheduler.until_next_candle(Duration::from_millis(1674950400000)),
+            Duration::from_secs(86400)
+        );
+    }
+
+    #[test]
+    fn test_wait_for_next_candle() {
+        let shutdown = Arc::new(Shutdown::new());
+        let scheduler = Scheduler::new(BinanceKlineInterval::Monthly, shutdown.clone());
+        shutdown.trigger();
+        assert!(!scheduler.wait_for_next_candle());
+        assert!(scheduler.is_stopped());
+    }
+}
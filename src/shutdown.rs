@@ -0,0 +1,160 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, Weak,
+    },
+    time::Duration,
+};
+
+use tokio::sync::watch;
+
+/// Stop request (e.g. CTRL + C) that interrupts every wait of the bot, both blocking and async ones.
+pub struct Shutdown {
+    stopped: Mutex<bool>,
+    condvar: Condvar,
+    /// Stops the loops that only check a flag, like the websocket's.
+    flags: Mutex<Vec<Weak<AtomicBool>>>,
+    /// Wakes up the async tasks.
+    tx: watch::Sender<bool>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        let (tx, _) = watch::channel(false);
+        Self {
+            stopped: Mutex::new(false),
+            condvar: Condvar::new(),
+            flags: Mutex::new(Vec::new()),
+            tx,
+        }
+    }
+
+    /// Requests everything to stop.
+    pub fn trigger(&self) {
+        let mut stopped = self.stopped.lock().unwrap();
+        *stopped = true;
+        for flag in self.flags.lock().unwrap().drain(..) {
+            if let Some(flag) = flag.upgrade() {
+                flag.store(false, Ordering::SeqCst);
+            }
+        }
+        drop(stopped);
+        self.condvar.notify_all();
+        self.tx.send_replace(true);
+    }
+
+    /// Requests everything to stop once the returned guard is dropped, which also happens when the thread panics.
+    pub fn trigger_on_drop(self: &Arc<Self>) -> ShutdownGuard {
+        ShutdownGuard(self.clone())
+    }
+
+    /// Returns a flag that's set until a stop is requested, its owner may also clear it to stop on its own.
+    pub fn running_flag(&self) -> Arc<AtomicBool> {
+        let stopped = self.stopped.lock().unwrap();
+        let flag = Arc::new(AtomicBool::new(!*stopped));
+        if !*stopped {
+            let mut flags = self.flags.lock().unwrap();
+            flags.retain(|flag| flag.strong_count() > 0);
+            flags.push(Arc::downgrade(&flag));
+        }
+        flag
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.stopped.lock().unwrap()
+    }
+
+    /// Blocks for `duration`, or until a stop is requested.
+    /// Returns `false` when it was interrupted.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let stopped = self.stopped.lock().unwrap();
+        let (stopped, _) = self
+            .condvar
+            .wait_timeout_while(stopped, duration, |stopped| !*stopped)
+            .unwrap();
+        !*stopped
+    }
+
+    /// Resolves once a stop is requested.
+    pub async fn wait(&self) {
+        let mut rx = self.tx.subscribe();
+        while !*rx.borrow_and_update() {
+            if rx.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Triggers the shutdown when dropped, see [`Shutdown::trigger_on_drop`].
+pub struct ShutdownGuard(Arc<Shutdown>);
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        self.0.trigger();
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::Ordering, Arc},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::Shutdown;
+
+    #[test]
+    fn test_sleep() {
+        let shutdown = Arc::new(Shutdown::new());
+        assert!(shutdown.sleep(Duration::from_millis(1)));
+
+        let s = shutdown.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            s.trigger();
+        });
+
+        let start = Instant::now();
+        assert!(!shutdown.sleep(Duration::from_secs(60)));
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(shutdown.is_triggered());
+
+        // Once stopped, sleeping returns right away.
+        assert!(!shutdown.sleep(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_running_flag() {
+        let shutdown = Shutdown::new();
+        let dropped = shutdown.running_flag();
+        drop(dropped);
+        let flag = shutdown.running_flag();
+        assert!(flag.load(Ordering::SeqCst));
+
+        shutdown.trigger();
+        assert!(!flag.load(Ordering::SeqCst));
+        assert!(!shutdown.running_flag().load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_trigger_on_drop() {
+        let shutdown = Arc::new(Shutdown::new());
+        let s = shutdown.clone();
+        let result = thread::spawn(move || {
+            let _guard = s.trigger_on_drop();
+            panic!("strategy failed");
+        })
+        .join();
+
+        assert!(result.is_err());
+        assert!(shutdown.is_triggered());
+    }
+}
@@ -0,0 +1,764 @@
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+
+use crate::market::{
+    Balance, Market, OcoOrder, Order, OrderFill, OrderSide, OrderStatus, OrderType,
+};
+
+/// Relative tolerance when comparing quantities, to absorb floating point rounding.
+const QUANTITY_TOLERANCE: f64 = 1e-12;
+
+/// A filled order on the simulated market.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatedFill {
+    pub symbol: String,
+    pub side: OrderSide,
+    pub price: f64,
+    /// Base asset quantity that changed hands, before commission.
+    pub base_quantity: f64,
+    /// Quote asset quantity that changed hands, before commission.
+    pub quote_quantity: f64,
+    /// Commission charged in the asset that was received.
+    pub commission: f64,
+}
+
+/// An order resting on the simulated book.
+#[derive(Debug, Clone)]
+struct SimulatedOrder {
+    order: Order,
+    /// Whether the stop price of a stop-limit order has been reached.
+    triggered: bool,
+    /// Balance reserved for the order, in the base asset for sells and the quote asset for buys.
+    locked: f64,
+    /// The other leg of an OCO order.
+    sibling: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct SimulatedState {
+    /// Balances that aren't reserved for open orders.
+    base_balance: f64,
+    quote_balance: f64,
+    price: Option<f64>,
+    fills: Vec<SimulatedFill>,
+    orders: Vec<SimulatedOrder>,
+    next_order_id: u64,
+}
+
+/// Paper trading market for a single symbol.
+/// Keeps balances in memory and fills market orders at the last price that was set.
+/// Limit and stop-limit orders rest on the book until a price that was set reaches them.
+pub struct SimulatedMarket {
+    symbol: String,
+    base_asset: String,
+    quote_asset: String,
+    /// Commission as a fraction of the received amount (e.g. `0.001` for 0.1%).
+    fee: f64,
+    state: Mutex<SimulatedState>,
+}
+
+impl SimulatedMarket {
+    /// Creates a market for `symbol`, which trades `base_asset` for `quote_asset`, that starts with the given quote balance.
+    pub fn new(
+        symbol: &str,
+        base_asset: &str,
+        quote_asset: &str,
+        quote_balance: f64,
+        fee: f64,
+    ) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            base_asset: base_asset.to_string(),
+            quote_asset: quote_asset.to_string(),
+            fee,
+            state: Mutex::new(SimulatedState {
+                quote_balance,
+                next_order_id: 1,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Sets the price at which the next orders will be filled, and fills the open orders it reaches.
+    pub fn set_price(&self, price: f64) {
+        let mut state = self.state.lock().unwrap();
+        state.price = Some(price);
+        self.match_orders(&mut state, price, false);
+    }
+
+    /// Returns the base asset balance that isn't reserved for open orders.
+    pub fn base_balance(&self) -> f64 {
+        self.state.lock().unwrap().base_balance
+    }
+
+    /// Returns the quote asset balance that isn't reserved for open orders.
+    pub fn quote_balance(&self) -> f64 {
+        self.state.lock().unwrap().quote_balance
+    }
+
+    /// Returns all fills so far, oldest first.
+    pub fn fills(&self) -> Vec<SimulatedFill> {
+        self.state.lock().unwrap().fills.clone()
+    }
+
+    /// Returns the current price, making sure the symbol is the one this market simulates.
+    fn price(&self, state: &SimulatedState, symbol: &str) -> anyhow::Result<f64> {
+        self.check_symbol(symbol)?;
+        state
+            .price
+            .ok_or(anyhow!("no price has been set for {}", symbol))
+    }
+
+    /// Returns the balance of an asset of the simulated symbol, including what's reserved for open orders.
+    fn balance(&self, state: &SimulatedState, asset: &str) -> Balance {
+        let (free, side) = if asset == self.base_asset {
+            (state.base_balance, OrderSide::Sell)
+        } else if asset == self.quote_asset {
+            (state.quote_balance, OrderSide::Buy)
+        } else {
+            return Balance {
+                asset: asset.to_string(),
+                free: 0.0,
+                locked: 0.0,
+            };
+        };
+
+        let locked = state
+            .orders
+            .iter()
+            .filter(|order| order.order.side == side)
+            .map(|order| order.locked)
+            .sum();
+        Balance {
+            asset: asset.to_string(),
+            free,
+            locked,
+        }
+    }
+
+    fn check_symbol(&self, symbol: &str) -> anyhow::Result<()> {
+        if symbol != self.symbol {
+            return Err(anyhow!(
+                "unknown symbol {}, this market only simulates {}",
+                symbol,
+                self.symbol
+            ));
+        }
+        Ok(())
+    }
+
+    /// Makes sure the free balance covers `needed` of the asset spent by an order on `side`.
+    fn check_balance(
+        &self,
+        state: &SimulatedState,
+        side: OrderSide,
+        needed: f64,
+    ) -> anyhow::Result<()> {
+        let (available, asset) = match side {
+            OrderSide::Buy => (state.quote_balance, &self.quote_asset),
+            OrderSide::Sell => (state.base_balance, &self.base_asset),
+        };
+
+        if needed > available * (1.0 + QUANTITY_TOLERANCE) {
+            return Err(anyhow!(
+                "insufficient balance: need {} {} but only {} is available",
+                needed,
+                asset,
+                available
+            ));
+        }
+        Ok(())
+    }
+
+    /// Exchanges `base_quantity` at `price` and returns the fill, taking the commission from the received asset.
+    fn execute(
+        &self,
+        state: &mut SimulatedState,
+        order_id: u64,
+        side: OrderSide,
+        base_quantity: f64,
+        price: f64,
+    ) -> OrderFill {
+        let quote_quantity = base_quantity * price;
+        let fill = match side {
+            OrderSide::Buy => {
+                let commission = base_quantity * self.fee;
+                state.quote_balance -= quote_quantity.min(state.quote_balance);
+                state.base_balance += base_quantity - commission;
+                OrderFill {
+                    order_id,
+                    quantity: base_quantity - commission,
+                    price,
+                    commission,
+                    commission_asset: self.base_asset.clone(),
+                }
+            }
+            OrderSide::Sell => {
+                let commission = quote_quantity * self.fee;
+                state.base_balance -= base_quantity.min(state.base_balance);
+                state.quote_balance += quote_quantity - commission;
+                OrderFill {
+                    order_id,
+                    quantity: base_quantity,
+                    price,
+                    commission,
+                    commission_asset: self.quote_asset.clone(),
+                }
+            }
+        };
+
+        state.fills.push(SimulatedFill {
+            symbol: self.symbol.clone(),
+            side,
+            price,
+            base_quantity,
+            quote_quantity,
+            commission: fill.commission,
+        });
+        fill
+    }
+
+    /// Validates a limit or stop-limit order and puts it on the book, unless it's a test order.
+    fn place(&self, order: Order, test: bool) -> anyhow::Result<Order> {
+        let mut state = self.state.lock().unwrap();
+        self.check_symbol(&order.symbol)?;
+
+        if order.quantity <= 0.0 || order.price <= 0.0 {
+            return Err(anyhow!(
+                "invalid order quantity {} or price {}",
+                order.quantity,
+                order.price
+            ));
+        }
+
+        let locked = match order.side {
+            OrderSide::Buy => order.quantity * order.price,
+            OrderSide::Sell => order.quantity,
+        };
+        self.check_balance(&state, order.side, locked)?;
+
+        // Like the binance test endpoint, test orders are validated but never executed.
+        if test {
+            return Ok(order);
+        }
+
+        let order = Order {
+            id: state.next_order_id,
+            ..order
+        };
+        state.next_order_id += 1;
+        self.lock(&mut state, order.side, locked);
+        state.orders.push(SimulatedOrder {
+            order,
+            triggered: false,
+            locked,
+            sibling: None,
+        });
+
+        let id = state.next_order_id - 1;
+        if let Some(price) = state.price {
+            self.match_orders(&mut state, price, true);
+        }
+        Ok(find(&state, id).unwrap().order.clone())
+    }
+
+    fn lock(&self, state: &mut SimulatedState, side: OrderSide, amount: f64) {
+        match side {
+            OrderSide::Buy => state.quote_balance -= amount.min(state.quote_balance),
+            OrderSide::Sell => state.base_balance -= amount.min(state.base_balance),
+        }
+    }
+
+    fn unlock(&self, state: &mut SimulatedState, side: OrderSide, amount: f64) {
+        match side {
+            OrderSide::Buy => state.quote_balance += amount,
+            OrderSide::Sell => state.base_balance += amount,
+        }
+    }
+
+    /// Closes an open order with the given status and gives back its reserved balance.
+    fn close(&self, state: &mut SimulatedState, id: u64, status: OrderStatus) {
+        let (side, locked) = match find_mut(state, id) {
+            Some(order) if order.order.status.is_open() => {
+                order.order.status = status;
+                let locked = order.locked;
+                order.locked = 0.0;
+                (order.order.side, locked)
+            }
+            _ => return,
+        };
+        self.unlock(state, side, locked);
+    }
+
+    /// Fills the open orders that `price` reaches.
+    /// Orders that were resting on the book fill at their limit price, orders that match as soon as they're placed at `price`.
+    fn match_orders(&self, state: &mut SimulatedState, price: f64, placed: bool) {
+        let ids: Vec<u64> = state
+            .orders
+            .iter()
+            .filter(|order| order.order.status.is_open())
+            .map(|order| order.order.id)
+            .collect();
+
+        for id in ids {
+            let order = match find_mut(state, id) {
+                Some(order) if order.order.status.is_open() => order,
+                _ => continue,
+            };
+
+            if order.order.order_type == OrderType::StopLossLimit && !order.triggered {
+                let stop_price = order.order.stop_price.unwrap_or(order.order.price);
+                order.triggered = match order.order.side {
+                    OrderSide::Buy => price >= stop_price,
+                    OrderSide::Sell => price <= stop_price,
+                };
+                if !order.triggered {
+                    continue;
+                }
+            }
+
+            let limit = order.order.price;
+            let reached = match order.order.side {
+                OrderSide::Buy => price <= limit,
+                OrderSide::Sell => price >= limit,
+            };
+            if !reached {
+                continue;
+            }
+
+            let fill_price = if placed || order.order.order_type == OrderType::StopLossLimit {
+                price
+            } else {
+                limit
+            };
+            let (side, quantity, sibling) = (order.order.side, order.order.quantity, order.sibling);
+
+            // Filling one leg of an OCO order cancels the other one.
+            if let Some(sibling) = sibling {
+                self.close(state, sibling, OrderStatus::Canceled);
+            }
+            self.close(state, id, OrderStatus::Filled);
+            let fill = self.execute(state, id, side, quantity, fill_price);
+
+            let order = find_mut(state, id).unwrap();
+            order.order.executed_quantity = quantity;
+            order.order.quote_quantity = quantity * fill.price;
+        }
+    }
+}
+
+fn find(state: &SimulatedState, id: u64) -> Option<&SimulatedOrder> {
+    state.orders.iter().find(|order| order.order.id == id)
+}
+
+fn find_mut(state: &mut SimulatedState, id: u64) -> Option<&mut SimulatedOrder> {
+    state.orders.iter_mut().find(|order| order.order.id == id)
+}
+
+impl Market for SimulatedMarket {
+    fn get_price(&self, symbol: &str) -> anyhow::Result<f64> {
+        let state = self.state.lock().unwrap();
+        self.price(&state, symbol)
+    }
+
+    fn place_buy_order(
+        &self,
+        symbol: &str,
+        quantity: f64,
+        test: bool,
+    ) -> anyhow::Result<OrderFill> {
+        let mut state = self.state.lock().unwrap();
+        let price = self.price(&state, symbol)?;
+
+        if quantity <= 0.0 {
+            return Err(anyhow!("invalid buy quantity {}", quantity));
+        }
+        self.check_balance(&state, OrderSide::Buy, quantity)?;
+
+        let quote_quantity = quantity.min(state.quote_balance);
+        let base_quantity = quote_quantity / price;
+
+        if test {
+            let commission = base_quantity * self.fee;
+            return Ok(OrderFill {
+                order_id: 0,
+                quantity: base_quantity - commission,
+                price,
+                commission,
+                commission_asset: self.base_asset.clone(),
+            });
+        }
+
+        let id = state.next_order_id;
+        state.next_order_id += 1;
+        Ok(self.execute(&mut state, id, OrderSide::Buy, base_quantity, price))
+    }
+
+    fn place_sell_order(
+        &self,
+        symbol: &str,
+        quantity: f64,
+        test: bool,
+    ) -> anyhow::Result<OrderFill> {
+        let mut state = self.state.lock().unwrap();
+        let price = self.price(&state, symbol)?;
+
+        if quantity <= 0.0 {
+            return Err(anyhow!("invalid sell quantity {}", quantity));
+        }
+        self.check_balance(&state, OrderSide::Sell, quantity)?;
+
+        let base_quantity = quantity.min(state.base_balance);
+
+        if test {
+            return Ok(OrderFill {
+                order_id: 0,
+                quantity: base_quantity,
+                price,
+                commission: base_quantity * price * self.fee,
+                commission_asset: self.quote_asset.clone(),
+            });
+        }
+
+        let id = state.next_order_id;
+        state.next_order_id += 1;
+        Ok(self.execute(&mut state, id, OrderSide::Sell, base_quantity, price))
+    }
+
+    fn place_limit_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        price: f64,
+        test: bool,
+    ) -> anyhow::Result<Order> {
+        self.place(
+            new_order(symbol, side, OrderType::Limit, quantity, price, None),
+            test,
+        )
+    }
+
+    fn place_stop_limit_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        stop_price: f64,
+        price: f64,
+        test: bool,
+    ) -> anyhow::Result<Order> {
+        self.place(
+            new_order(
+                symbol,
+                side,
+                OrderType::StopLossLimit,
+                quantity,
+                price,
+                Some(stop_price),
+            ),
+            test,
+        )
+    }
+
+    fn place_oco_order(
+        &self,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        price: f64,
+        stop_price: f64,
+        stop_limit_price: f64,
+    ) -> anyhow::Result<OcoOrder> {
+        let mut state = self.state.lock().unwrap();
+        let current_price = self.price(&state, symbol)?;
+
+        // Binance requires the limit and stop on opposite sides of the current price.
+        let valid = match side {
+            OrderSide::Sell => price > current_price && stop_price < current_price,
+            OrderSide::Buy => price < current_price && stop_price > current_price,
+        };
+        if quantity <= 0.0 || !valid {
+            return Err(anyhow!(
+                "invalid OCO order: quantity {}, price {}, stop price {} at current price {}",
+                quantity,
+                price,
+                stop_price,
+                current_price
+            ));
+        }
+
+        // Both legs share the same reserved balance, enough for the more expensive one.
+        let locked = match side {
+            OrderSide::Buy => quantity * price.max(stop_limit_price),
+            OrderSide::Sell => quantity,
+        };
+        self.check_balance(&state, side, locked)?;
+        self.lock(&mut state, side, locked);
+
+        let list_id = state.next_order_id;
+        let limit_id = list_id + 1;
+        let stop_id = list_id + 2;
+        state.next_order_id += 3;
+
+        let limit = Order {
+            id: limit_id,
+            ..new_order(symbol, side, OrderType::LimitMaker, quantity, price, None)
+        };
+        let stop = Order {
+            id: stop_id,
+            ..new_order(
+                symbol,
+                side,
+                OrderType::StopLossLimit,
+                quantity,
+                stop_limit_price,
+                Some(stop_price),
+            )
+        };
+        state.orders.push(SimulatedOrder {
+            order: limit.clone(),
+            triggered: false,
+            locked,
+            sibling: Some(stop_id),
+        });
+        state.orders.push(SimulatedOrder {
+            order: stop.clone(),
+            triggered: false,
+            locked: 0.0,
+            sibling: Some(limit_id),
+        });
+
+        Ok(OcoOrder {
+            id: list_id,
+            limit,
+            stop,
+        })
+    }
+
+    fn get_order(&self, symbol: &str, id: u64) -> anyhow::Result<Order> {
+        self.check_symbol(symbol)?;
+        let state = self.state.lock().unwrap();
+        find(&state, id)
+            .map(|order| order.order.clone())
+            .ok_or(anyhow!("unknown order {}", id))
+    }
+
+    fn cancel_order(&self, symbol: &str, id: u64) -> anyhow::Result<Order> {
+        self.check_symbol(symbol)?;
+        let mut state = self.state.lock().unwrap();
+        let order = find(&state, id).ok_or(anyhow!("unknown order {}", id))?;
+        if !order.order.status.is_open() {
+            return Err(anyhow!(
+                "order {} can't be canceled, it's {:?}",
+                id,
+                order.order.status
+            ));
+        }
+
+        // Like on binance, canceling one leg of an OCO order cancels the whole list.
+        if let Some(sibling) = order.sibling {
+            self.close(&mut state, sibling, OrderStatus::Canceled);
+        }
+        self.close(&mut state, id, OrderStatus::Canceled);
+        Ok(find(&state, id).unwrap().order.clone())
+    }
+
+    fn open_orders(&self, symbol: &str) -> anyhow::Result<Vec<Order>> {
+        self.check_symbol(symbol)?;
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .orders
+            .iter()
+            .filter(|order| order.order.status.is_open())
+            .map(|order| order.order.clone())
+            .collect())
+    }
+
+    fn get_balance(&self, asset: &str) -> anyhow::Result<Balance> {
+        let state = self.state.lock().unwrap();
+        Ok(self.balance(&state, asset))
+    }
+
+    fn get_balances(&self) -> anyhow::Result<Vec<Balance>> {
+        let state = self.state.lock().unwrap();
+        Ok([&self.base_asset, &self.quote_asset]
+            .iter()
+            .map(|asset| self.balance(&state, asset))
+            .filter(|balance| balance.total() > 0.0)
+            .collect())
+    }
+}
+
+fn new_order(
+    symbol: &str,
+    side: OrderSide,
+    order_type: OrderType,
+    quantity: f64,
+    price: f64,
+    stop_price: Option<f64>,
+) -> Order {
+    Order {
+        id: 0,
+        symbol: symbol.to_string(),
+        side,
+        order_type,
+        status: OrderStatus::New,
+        price,
+        stop_price,
+        quantity,
+        executed_quantity: 0.0,
+        quote_quantity: 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::market::{Market, OrderSide, OrderStatus};
+
+    use super::SimulatedMarket;
+
+    #[test]
+    fn test_buy_and_sell() {
+        let market = SimulatedMarket::new("BTCUSDT", "BTC", "USDT", 100.0, 0.01);
+        market.set_price(10.0);
+        assert_eq!(market.get_price("BTCUSDT").unwrap(), 10.0);
+
+        let fill = market.place_buy_order("BTCUSDT", 50.0, false).unwrap();
+        assert_eq!(fill.quantity, 4.95);
+        assert_eq!(fill.price, 10.0);
+        assert_eq!(fill.commission_asset, "BTC");
+        assert_eq!(market.quote_balance(), 50.0);
+        assert_eq!(market.base_balance(), 4.95);
+
+        market.set_price(20.0);
+        let fill = market.place_sell_order("BTCUSDT", 2.475, false).unwrap();
+        assert_eq!(fill.quantity, 2.475);
+        assert_eq!(fill.commission_asset, "USDT");
+        assert_eq!(market.base_balance(), 2.475);
+        assert_eq!(market.quote_balance(), 50.0 + 49.5 - 0.495);
+
+        let fills = market.fills();
+        assert_eq!(fills.len(), 2);
+        assert_eq!(fills[0].side, OrderSide::Buy);
+        assert_eq!(fills[0].commission, 0.05);
+        assert_eq!(fills[1].side, OrderSide::Sell);
+        assert_eq!(fills[1].price, 20.0);
+    }
+
+    #[test]
+    fn test_test_orders_are_not_executed() {
+        let market = SimulatedMarket::new("BTCUSDT", "BTC", "USDT", 100.0, 0.0);
+        market.set_price(10.0);
+        let fill = market.place_buy_order("BTCUSDT", 50.0, true).unwrap();
+        assert_eq!(fill.quantity, 5.0);
+        assert_eq!(market.quote_balance(), 100.0);
+        assert_eq!(market.base_balance(), 0.0);
+        assert!(market.fills().is_empty());
+    }
+
+    #[test]
+    fn test_rejected_orders() {
+        let market = SimulatedMarket::new("BTCUSDT", "BTC", "USDT", 100.0, 0.0);
+        assert!(market.get_price("BTCUSDT").is_err());
+
+        market.set_price(10.0);
+        assert!(market.get_price("ETHUSDT").is_err());
+        assert!(market.place_buy_order("BTCUSDT", 150.0, false).is_err());
+        assert!(market.place_sell_order("BTCUSDT", 1.0, false).is_err());
+        assert!(market.place_buy_order("BTCUSDT", 0.0, false).is_err());
+        assert!(market
+            .place_limit_order("BTCUSDT", OrderSide::Buy, 20.0, 9.0, false)
+            .is_err());
+        assert!(market.fills().is_empty());
+    }
+
+    #[test]
+    fn test_limit_orders() {
+        let market = SimulatedMarket::new("BTCUSDT", "BTC", "USDT", 100.0, 0.0);
+        market.set_price(10.0);
+        market.place_buy_order("BTCUSDT", 50.0, false).unwrap();
+
+        // The quantity is reserved until the order fills.
+        let order = market
+            .place_limit_order("BTCUSDT", OrderSide::Sell, 5.0, 12.0, false)
+            .unwrap();
+        assert_eq!(order.status, OrderStatus::New);
+        assert_eq!(market.base_balance(), 0.0);
+        assert_eq!(market.open_orders("BTCUSDT").unwrap(), vec![order.clone()]);
+
+        market.set_price(11.0);
+        assert_eq!(
+            market.get_order("BTCUSDT", order.id).unwrap().status,
+            OrderStatus::New
+        );
+
+        // A price beyond the limit still fills at the limit.
+        market.set_price(13.0);
+        let order = market.get_order("BTCUSDT", order.id).unwrap();
+        assert_eq!(order.status, OrderStatus::Filled);
+        assert_eq!(order.average_price(), Some(12.0));
+        assert_eq!(market.quote_balance(), 110.0);
+        assert!(market.open_orders("BTCUSDT").unwrap().is_empty());
+        assert!(market.cancel_order("BTCUSDT", order.id).is_err());
+
+        // Canceling gives back the reserved balance.
+        let order = market
+            .place_limit_order("BTCUSDT", OrderSide::Buy, 10.0, 8.0, false)
+            .unwrap();
+        assert_eq!(market.quote_balance(), 30.0);
+        let balance = market.get_balance("USDT").unwrap();
+        assert_eq!(
+            (balance.free, balance.locked, balance.total()),
+            (30.0, 80.0, 110.0)
+        );
+        assert_eq!(market.get_balance("ETH").unwrap().total(), 0.0);
+        assert_eq!(market.get_balances().unwrap(), vec![balance]);
+        let order = market.cancel_order("BTCUSDT", order.id).unwrap();
+        assert_eq!(order.status, OrderStatus::Canceled);
+        assert_eq!(market.quote_balance(), 110.0);
+    }
+
+    #[test]
+    fn test_stop_limit_and_oco_orders() {
+        let market = SimulatedMarket::new("BTCUSDT", "BTC", "USDT", 100.0, 0.0);
+        market.set_price(10.0);
+        market.place_buy_order("BTCUSDT", 100.0, false).unwrap();
+
+        // Take profit at 12 or stop out at 9.
+        let oco = market
+            .place_oco_order("BTCUSDT", OrderSide::Sell, 10.0, 12.0, 9.0, 8.5)
+            .unwrap();
+        assert_eq!(market.open_orders("BTCUSDT").unwrap().len(), 2);
+        assert_eq!(market.base_balance(), 0.0);
+
+        market.set_price(8.8);
+        let stop = market.get_order("BTCUSDT", oco.stop.id).unwrap();
+        assert_eq!(stop.status, OrderStatus::Filled);
+        assert_eq!(stop.average_price(), Some(8.8));
+        assert_eq!(
+            market.get_order("BTCUSDT", oco.limit.id).unwrap().status,
+            OrderStatus::Canceled
+        );
+        assert_eq!(market.quote_balance(), 88.0);
+        assert!(market.open_orders("BTCUSDT").unwrap().is_empty());
+
+        // A stop-limit order only becomes a limit order once the stop price is reached.
+        let order = market
+            .place_stop_limit_order("BTCUSDT", OrderSide::Buy, 4.0, 9.5, 10.0, false)
+            .unwrap();
+        market.set_price(9.0);
+        assert!(market
+            .get_order("BTCUSDT", order.id)
+            .unwrap()
+            .status
+            .is_open());
+        market.set_price(9.6);
+        assert_eq!(
+            market.get_order("BTCUSDT", order.id).unwrap().status,
+            OrderStatus::Filled
+        );
+        assert_eq!(market.base_balance(), 4.0);
+    }
+}
@@ -0,0 +1,157 @@
+use std::{sync::Mutex, time::Duration};
+
+use crate::{
+    dataset::Label, events::TradeEvent, model_store::ModelMetadata, position::Position, utils::now,
+};
+
+/// What the bot is up to, kept up to date by the strategy thread and queried through telegram.
+pub struct Status {
+    snapshot: Mutex<StatusSnapshot>,
+}
+
+/// State of the bot at one point in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusSnapshot {
+    pub symbol: String,
+    /// Whether orders are only tested.
+    pub test: bool,
+    /// Unix timestamp of when the bot started.
+    pub started_at: Duration,
+    /// Whether opening new positions was paused.
+    pub paused: bool,
+    pub position: Position,
+    /// Profit of the positions closed since the bot started, in the quote asset.
+    pub realized_profit: f64,
+    pub closed_positions: u32,
+    pub profitable_positions: u32,
+    /// The model predictions are made with.
+    pub model: Option<ModelMetadata>,
+    pub last_prediction: Option<PredictionStatus>,
+    /// Trade request that's waiting for an approval, see [`TradeEvent::ApprovalRequested`].
+    pub pending_approval: Option<u64>,
+}
+
+/// The most recent prediction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PredictionStatus {
+    /// Unix timestamp of when it was made.
+    pub at: Duration,
+    pub label: Label,
+    pub predicted: f64,
+    /// Open and current price of the candle that was forming.
+    pub open: f64,
+    pub close: f64,
+}
+
+impl Status {
+    pub fn new(symbol: &str, test: bool) -> Self {
+        Self {
+            snapshot: Mutex::new(StatusSnapshot {
+                symbol: symbol.to_string(),
+                test,
+                started_at: now(),
+                paused: false,
+                position: Position::Flat,
+                realized_profit: 0.0,
+                closed_positions: 0,
+                profitable_positions: 0,
+                model: None,
+                last_prediction: None,
+                pending_approval: None,
+            }),
+        }
+    }
+
+    pub fn snapshot(&self) -> StatusSnapshot {
+        self.snapshot.lock().unwrap().clone()
+    }
+
+    pub fn set_position(&self, position: Position) {
+        self.snapshot.lock().unwrap().position = position;
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.snapshot.lock().unwrap().paused = paused;
+    }
+
+    pub fn set_model(&self, model: ModelMetadata) {
+        self.snapshot.lock().unwrap().model = Some(model);
+    }
+
+    /// Marks the trade request as decided.
+    /// Returns `false` when it isn't pending, because it expired or was already decided.
+    pub fn decide_approval(&self, request: u64) -> bool {
+        let mut snapshot = self.snapshot.lock().unwrap();
+        if snapshot.pending_approval != Some(request) {
+            return false;
+        }
+        snapshot.pending_approval = None;
+        true
+    }
+
+    /// Keeps track of the predictions, approval requests and profits reported by the event.
+    pub fn record(&self, event: &TradeEvent) {
+        let mut snapshot = self.snapshot.lock().unwrap();
+        match event {
+            TradeEvent::Prediction {
+                label,
+                predicted,
+                open,
+                close,
+                ..
+            } => {
+                snapshot.last_prediction = Some(PredictionStatus {
+                    at: now(),
+                    label: *label,
+                    predicted: *predicted,
+                    open: *open,
+                    close: *close,
+                })
+            }
+            TradeEvent::ApprovalRequested { request, .. } => {
+                snapshot.pending_approval = Some(*request)
+            }
+            TradeEvent::TradeApproved { .. } | TradeEvent::TradeSkipped { .. } => {
+                snapshot.pending_approval = None
+            }
+            TradeEvent::PositionClosed { profit, .. } => {
+                snapshot.realized_profit += profit;
+                snapshot.closed_positions += 1;
+                if *profit > 0.0 {
+                    snapshot.profitable_positions += 1;
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{events::TradeEvent, position::ExitReason};
+
+    use super::Status;
+
+    #[test]
+    fn test_record() {
+        let status = Status::new("BTCUSDT", true);
+        let closed = |profit: f64| TradeEvent::PositionClosed {
+            symbol: String::from("BTCUSDT"),
+            quantity: 0.002,
+            entry_price: 20000.0,
+            exit_price: 20000.0,
+            reason: ExitReason::StopLoss,
+            profit,
+            profit_percentage: 0.0,
+        };
+        status.record(&closed(2.5));
+        status.record(&closed(-1.0));
+        status.record(&TradeEvent::Shutdown);
+
+        let snapshot = status.snapshot();
+        assert_eq!(snapshot.realized_profit, 1.5);
+        assert_eq!(snapshot.closed_positions, 2);
+        assert_eq!(snapshot.profitable_positions, 1);
+        assert_eq!(snapshot.last_prediction, None);
+    }
+}
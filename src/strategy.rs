@@ -1,36 +1,254 @@
-use crate::{config::AppConfig, dataset::DataSet, market::Market, model::Model};
-use std::{
-    sync::{atomic::AtomicBool, mpsc::Sender, Arc},
-    time::Instant,
+use crate::{
+    config::AppConfig,
+    control::Controls,
+    dataset::{Candle, DataSet},
+    events::{SkipReason, TradeEvent},
+    kline_store::KlineStore,
+    market::Market,
+    model::Model,
+    model_store::{ModelMetadata, ModelStore, TrainedModel},
+    notifications::NotificationSender,
+    scheduler::Scheduler,
+    shutdown::Shutdown,
+    status::Status,
+    utils::{calculate_profit, now},
 };
+use anyhow::anyhow;
+use std::{cell::RefCell, sync::Arc, time::Duration};
+
+/// How often to check whether a trade was approved.
+const APPROVAL_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
 pub trait Strategy {
-    fn execute(&self, running: Arc<AtomicBool>, tx: &Sender<String>);
+    /// Trades until a shutdown is requested through the scheduler.
+    fn execute(&self, tx: &NotificationSender<TradeEvent>);
 }
 
 pub struct LightGBMStrategy<M: Market> {
     pub config: AppConfig,
     pub model: Model,
     pub market: M,
+    pub models: ModelStore,
+    pub klines: KlineStore,
+    pub scheduler: Scheduler,
+    /// Shared with the telegram bot, which reports it.
+    pub status: Arc<Status>,
+    /// Commands sent over telegram.
+    pub controls: Controls,
+    /// The model predictions are made with, loaded or trained on first use.
+    trained: RefCell<Option<TrainedModel>>,
 }
 
 impl<M: Market> LightGBMStrategy<M> {
-    pub fn new(config: AppConfig, market: M) -> Self {
+    pub fn new(
+        config: AppConfig,
+        market: M,
+        shutdown: Arc<Shutdown>,
+        status: Arc<Status>,
+        controls: Controls,
+    ) -> Self {
         Self {
+            model: Model::with_params(&config.model),
+            models: ModelStore::new(&config.training.model_dir),
+            klines: KlineStore::new(&config.dataset.kline_dir),
+            scheduler: Scheduler::new(config.interval, shutdown),
+            status,
+            controls,
+            trained: RefCell::new(None),
             config,
             market,
-            model: Model::new(),
         }
     }
 
-    pub fn train_model(&self, dataset: DataSet) -> anyhow::Result<lightgbm::Booster> {
-        // Train the model.
-        info!("Training model");
-        let start = Instant::now();
-        let booster = self.model.train(dataset)?;
-        let end = Instant::now();
-        let elapsed = end.duration_since(start);
-        info!("Model trained successfully! Time elapsed: {:?}", elapsed);
-        Ok(booster)
+    /// Predicts the label of the candle after the last of the given closed candles.
+    /// The model is retrained on these candles first when it's due.
+    pub fn predict(
+        &self,
+        candles: &[Candle],
+        tx: &NotificationSender<TradeEvent>,
+    ) -> anyhow::Result<f64> {
+        let mut trained = self.trained.borrow_mut();
+        if trained.is_none() {
+            *trained = self.load_model()?;
+            if let Some(model) = trained.as_ref() {
+                self.status.set_model(model.metadata.clone());
+            }
+        }
+        if self.should_retrain(trained.as_ref(), candles) {
+            let model = self.train_and_save(candles, tx)?;
+            self.status.set_model(model.metadata.clone());
+            *trained = Some(model);
+        }
+        let model = trained.as_ref().unwrap();
+
+        let features = DataSet::latest_features(candles, &self.config.dataset.features)
+            .ok_or(anyhow!("not enough candles to compute the features"))?;
+        debug!(
+            "Predicting with model {} from features {:?}.",
+            model.metadata.version, features
+        );
+        let prediction = model.booster.predict(vec![features])?;
+
+        Ok(prediction[0][0])
+    }
+
+    /// Sends an event, after keeping track of what it reports in the status.
+    pub fn notify(&self, tx: &NotificationSender<TradeEvent>, event: TradeEvent) {
+        self.status.record(&event);
+        tx.send(event);
+    }
+
+    /// Applies the control commands sent since the last call, and shows whether trading is paused in the status.
+    pub fn receive_controls(&self) {
+        self.controls.receive();
+        self.status.set_paused(self.controls.is_paused());
+    }
+
+    /// Asks for approval to buy at the current candle, and waits for it until the configured timeout or the end of the candle, whichever comes first.
+    /// Returns who approved it, or why the trade should be skipped.
+    pub fn await_approval(
+        &self,
+        tx: &NotificationSender<TradeEvent>,
+        predicted: f64,
+        open: f64,
+        close: f64,
+    ) -> Result<String, SkipReason> {
+        let request = now().as_millis() as u64;
+        // The prediction is only meant for the current candle.
+        let timeout = Duration::from_secs(self.config.trade.approval_timeout)
+            .min(self.scheduler.until_next_candle(now()));
+        let (expected_profit, expected_profit_percentage) =
+            calculate_profit(self.config.trade.amount, close, predicted);
+        let event = TradeEvent::ApprovalRequested {
+            request,
+            symbol: self.config.symbol.clone(),
+            amount: self.config.trade.amount,
+            predicted,
+            open,
+            close,
+            expected_profit,
+            expected_profit_percentage,
+            timeout: timeout.as_secs(),
+        };
+        info!("{}", event);
+        self.notify(tx, event);
+
+        let deadline = now() + timeout;
+        loop {
+            self.receive_controls();
+            if let Some(decision) = self.controls.take_decision(request) {
+                if !decision.approved {
+                    return Err(SkipReason::Rejected { by: decision.by });
+                }
+
+                let event = TradeEvent::TradeApproved {
+                    request,
+                    symbol: self.config.symbol.clone(),
+                    by: decision.by.clone(),
+                };
+                info!("{}", event);
+                self.notify(tx, event);
+                return Ok(decision.by);
+            }
+
+            let remaining = deadline.saturating_sub(now());
+            if remaining.is_zero() || !self.scheduler.sleep(remaining.min(APPROVAL_POLL_INTERVAL)) {
+                return Err(SkipReason::ApprovalExpired);
+            }
+        }
+    }
+
+    /// Version of the model predictions are made with, if one was loaded or trained yet.
+    pub fn model_version(&self) -> Option<String> {
+        self.trained
+            .borrow()
+            .as_ref()
+            .map(|model| model.metadata.version.clone())
+    }
+
+    /// Loads the pinned model, or the latest saved one if it was trained the way the config describes.
+    fn load_model(&self) -> anyhow::Result<Option<TrainedModel>> {
+        let model = match &self.config.training.model {
+            Some(version) => Some(self.models.load(version)?),
+            None => self.models.latest(&self.config.symbol)?,
+        };
+
+        match model {
+            Some(model) if !self.is_compatible(&model.metadata) => {
+                if self.config.training.model.is_some() {
+                    return Err(anyhow!(
+                        "model {} was trained with different features or label than configured",
+                        model.metadata.version
+                    ));
+                }
+                warn!(
+                    "Ignoring model {} because it was trained with different parameters, features or label than configured.",
+                    model.metadata.version
+                );
+                Ok(None)
+            }
+            Some(model) => {
+                info!(
+                    "Loaded model {} trained on candles {} to {} (rmse: {}, mae: {}).",
+                    model.metadata.version,
+                    model.metadata.window_start,
+                    model.metadata.window_end,
+                    model.metadata.metrics.rmse,
+                    model.metadata.metrics.mae
+                );
+                Ok(Some(model))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Whether a model predicts the configured label from the configured features.
+    /// Pinned models are used regardless of the configured parameters.
+    fn is_compatible(&self, metadata: &ModelMetadata) -> bool {
+        metadata.features == self.config.dataset.features.names()
+            && metadata.label == self.config.dataset.label
+            && (self.config.training.model.is_some() || &metadata.params == self.model.params())
+    }
+
+    /// Pinned models are never retrained, others once enough new candles have closed.
+    fn should_retrain(&self, model: Option<&TrainedModel>, candles: &[Candle]) -> bool {
+        match model {
+            None => true,
+            Some(_) if self.config.training.model.is_some() => false,
+            Some(model) => {
+                let new_candles = candles
+                    .iter()
+                    .filter(|candle| candle.open_time > model.metadata.window_end)
+                    .count();
+                new_candles as u64 >= self.config.training.retrain_interval
+            }
+        }
+    }
+
+    /// Trains a new model on the given candles and saves it.
+    fn train_and_save(
+        &self,
+        candles: &[Candle],
+        tx: &NotificationSender<TradeEvent>,
+    ) -> anyhow::Result<TrainedModel> {
+        let model = TrainedModel::train(
+            &self.model,
+            &self.config.symbol,
+            &self.config.dataset,
+            candles,
+        )?;
+        self.models.save(&model)?;
+
+        let event = TradeEvent::ModelTrained {
+            symbol: self.config.symbol.clone(),
+            version: model.metadata.version.clone(),
+            samples: model.metadata.metrics.samples,
+            rmse: model.metadata.metrics.rmse,
+            mae: model.metadata.metrics.mae,
+        };
+        info!("{}", event);
+        self.notify(tx, event);
+
+        Ok(model)
     }
 }
@@ -0,0 +1,284 @@
+use std::fmt;
+
+use anyhow::Context;
+use binance::model::{Filters, Symbol};
+
+/// The trading rules binance enforces on orders for a symbol, from its exchange info.
+/// See https://binance-docs.github.io/apidocs/spot/en/#filters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolFilters {
+    pub symbol: String,
+    /// Asset that is bought and sold, like `BTC` in `BTCUSDT`.
+    pub base_asset: String,
+    /// Asset that is spent on buying, like `USDT` in `BTCUSDT`.
+    pub quote_asset: String,
+    /// `PRICE_FILTER`, a value of 0 disables that part of the filter.
+    pub min_price: f64,
+    pub max_price: f64,
+    pub tick_size: f64,
+    /// `LOT_SIZE`, limits the base asset quantity of an order.
+    pub min_qty: f64,
+    pub max_qty: f64,
+    pub step_size: f64,
+    /// `MIN_NOTIONAL`, the minimum value of an order in the quote asset.
+    pub min_notional: f64,
+    /// Whether `min_notional` applies to market orders as well.
+    pub apply_to_market: bool,
+    /// Number of decimals binance accepts for quote asset amounts.
+    pub quote_precision: u32,
+}
+
+/// An order that binance would reject, with the filter it violates.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterError {
+    PriceFilter { price: f64, min: f64, max: f64 },
+    LotSize { quantity: f64, min: f64, max: f64 },
+    MinNotional { notional: f64, min: f64 },
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::PriceFilter { price, min, max } => write!(
+                f,
+                "PRICE_FILTER: price {} is outside of the allowed range {} to {}",
+                price, min, max
+            ),
+            FilterError::LotSize { quantity, min, max } => write!(
+                f,
+                "LOT_SIZE: quantity {} is outside of the allowed range {} to {}",
+                quantity, min, max
+            ),
+            FilterError::MinNotional { notional, min } => write!(
+                f,
+                "MIN_NOTIONAL: order value {} is below the minimum of {}",
+                notional, min
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+impl SymbolFilters {
+    pub fn from_binance(symbol: &Symbol) -> anyhow::Result<Self> {
+        let mut filters = SymbolFilters {
+            symbol: symbol.symbol.clone(),
+            base_asset: symbol.base_asset.clone(),
+            quote_asset: symbol.quote_asset.clone(),
+            min_price: 0.0,
+            max_price: 0.0,
+            tick_size: 0.0,
+            min_qty: 0.0,
+            max_qty: 0.0,
+            step_size: 0.0,
+            min_notional: 0.0,
+            apply_to_market: false,
+            quote_precision: symbol.quote_precision as u32,
+        };
+
+        for filter in &symbol.filters {
+            match filter {
+                Filters::PriceFilter {
+                    min_price,
+                    max_price,
+                    tick_size,
+                } => {
+                    filters.min_price = parse(min_price)?;
+                    filters.max_price = parse(max_price)?;
+                    filters.tick_size = parse(tick_size)?;
+                }
+                Filters::LotSize {
+                    min_qty,
+                    max_qty,
+                    step_size,
+                } => {
+                    filters.min_qty = parse(min_qty)?;
+                    filters.max_qty = parse(max_qty)?;
+                    filters.step_size = parse(step_size)?;
+                }
+                Filters::MinNotional {
+                    notional,
+                    min_notional,
+                    apply_to_market,
+                    ..
+                } => {
+                    if let Some(min_notional) = min_notional.as_ref().or(notional.as_ref()) {
+                        filters.min_notional = parse(min_notional)?;
+                    }
+                    filters.apply_to_market = apply_to_market.unwrap_or(true);
+                }
+                _ => (),
+            }
+        }
+
+        Ok(filters)
+    }
+
+    /// Rounds a price down to the tick size.
+    pub fn round_price(&self, price: f64) -> f64 {
+        round_down(price, self.tick_size)
+    }
+
+    /// Rounds a base asset quantity down to the step size.
+    pub fn round_quantity(&self, quantity: f64) -> f64 {
+        round_down(quantity, self.step_size)
+    }
+
+    /// Rounds a quote asset amount down to the quote precision.
+    pub fn round_amount(&self, amount: f64) -> f64 {
+        round_down(amount, 10f64.powi(-(self.quote_precision as i32)))
+    }
+
+    pub fn check_price(&self, price: f64) -> Result<(), FilterError> {
+        if price <= 0.0
+            || (self.min_price > 0.0 && price < self.min_price)
+            || (self.max_price > 0.0 && price > self.max_price)
+        {
+            return Err(FilterError::PriceFilter {
+                price,
+                min: self.min_price,
+                max: self.max_price,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn check_quantity(&self, quantity: f64) -> Result<(), FilterError> {
+        if quantity <= 0.0
+            || quantity < self.min_qty
+            || (self.max_qty > 0.0 && quantity > self.max_qty)
+        {
+            return Err(FilterError::LotSize {
+                quantity,
+                min: self.min_qty,
+                max: self.max_qty,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks the value of an order in the quote asset.
+    pub fn check_notional(&self, notional: f64, market: bool) -> Result<(), FilterError> {
+        if (!market || self.apply_to_market) && notional < self.min_notional {
+            return Err(FilterError::MinNotional {
+                notional,
+                min: self.min_notional,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the quote asset amount to spend on a market buy, rounded to what binance accepts.
+    pub fn market_buy_amount(&self, amount: f64) -> Result<f64, FilterError> {
+        let amount = self.round_amount(amount);
+        self.check_notional(amount, true)?;
+        Ok(amount)
+    }
+
+    /// Returns the quantity and price of a limit order, rounded to the step and tick size.
+    pub fn limit_order(&self, quantity: f64, price: f64) -> Result<(f64, f64), FilterError> {
+        let quantity = self.round_quantity(quantity);
+        let price = self.round_price(price);
+        self.check_price(price)?;
+        self.check_quantity(quantity)?;
+        self.check_notional(quantity * price, false)?;
+        Ok((quantity, price))
+    }
+
+    /// Returns the base asset quantity to sell with a market order at about `price`, rounded to the step size.
+    pub fn market_sell_quantity(&self, quantity: f64, price: f64) -> Result<f64, FilterError> {
+        let quantity = self.round_quantity(quantity);
+        self.check_quantity(quantity)?;
+        self.check_notional(quantity * price, true)?;
+        Ok(quantity)
+    }
+}
+
+fn parse(value: &str) -> anyhow::Result<f64> {
+    value
+        .parse()
+        .with_context(|| format!("invalid filter value {}", value))
+}
+
+/// Rounds `value` down to a multiple of `step`, a step of 0 leaves it as is.
+fn round_down(value: f64, step: f64) -> f64 {
+    if step <= 0.0 {
+        return value;
+    }
+
+    // Steps are powers of ten like 0.001, so rounding to their decimals removes floating point noise like 0.30000000000000004.
+    let decimals = (-step.log10()).ceil().max(0.0) as i32;
+    let scale = 10f64.powi(decimals);
+    let steps = (value / step + 1e-9).floor();
+    (steps * step * scale).round() / scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FilterError, SymbolFilters};
+
+    fn filters() -> SymbolFilters {
+        SymbolFilters {
+            symbol: String::from("BTCUSDT"),
+            base_asset: String::from("BTC"),
+            quote_asset: String::from("USDT"),
+            min_price: 0.01,
+            max_price: 1_000_000.0,
+            tick_size: 0.01,
+            min_qty: 0.00001,
+            max_qty: 9000.0,
+            step_size: 0.00001,
+            min_notional: 10.0,
+            apply_to_market: true,
+            quote_precision: 8,
+        }
+    }
+
+    #[test]
+    fn test_round() {
+        let filters = filters();
+        assert_eq!(filters.round_quantity(0.123456789), 0.12345);
+        assert_eq!(filters.round_quantity(0.3), 0.3);
+        assert_eq!(filters.round_price(23456.789), 23456.78);
+        assert_eq!(filters.round_amount(50.123456789), 50.12345678);
+    }
+
+    #[test]
+    fn test_check() {
+        let filters = filters();
+        assert_eq!(filters.market_buy_amount(50.0), Ok(50.0));
+        assert_eq!(
+            filters.market_buy_amount(5.0),
+            Err(FilterError::MinNotional {
+                notional: 5.0,
+                min: 10.0
+            })
+        );
+        assert_eq!(
+            filters.market_sell_quantity(0.0021999, 23000.0),
+            Ok(0.00219)
+        );
+        assert_eq!(
+            filters.market_sell_quantity(0.000001, 23000.0),
+            Err(FilterError::LotSize {
+                quantity: 0.0,
+                min: 0.00001,
+                max: 9000.0
+            })
+        );
+        assert!(matches!(
+            filters.market_sell_quantity(0.0001, 23000.0),
+            Err(FilterError::MinNotional { .. })
+        ));
+        assert_eq!(
+            filters.limit_order(0.0021999, 23456.789),
+            Ok((0.00219, 23456.78))
+        );
+        assert!(filters.check_price(23000.0).is_ok());
+        assert!(matches!(
+            filters.check_price(0.001),
+            Err(FilterError::PriceFilter { .. })
+        ));
+    }
+}
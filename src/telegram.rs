@@ -0,0 +1,634 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use teloxide::{
+    dispatching::{Dispatcher, UpdateFilterExt},
+    dptree,
+    payloads::{AnswerCallbackQuerySetters, SendMessageSetters},
+    requests::{Requester, ResponseResult},
+    respond,
+    types::{
+        BotCommand, CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, Message,
+        Update, User, UserId,
+    },
+    Bot,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    config::TelegramConfig,
+    control::{Control, Decision},
+    events::TradeEvent,
+    logging::RecentLogs,
+    notifier::Notifier,
+    position::Position,
+    shutdown::Shutdown,
+    status::{Status, StatusSnapshot},
+    utils::{format_duration, format_timestamp, now},
+};
+
+/// Amount of log lines `/logs` sends when no amount is given.
+const DEFAULT_LOG_LINES: usize = 20;
+
+/// Telegram refuses messages longer than this many characters.
+const MAX_MESSAGE_LENGTH: usize = 4096;
+
+/// Commands with their description, as listed in the telegram menu.
+const COMMANDS: [(&str, &str); 10] = [
+    ("status", "Uptime, open position and profit"),
+    ("position", "The open position and its target"),
+    ("pnl", "Profit realized since the bot started"),
+    ("model", "The model in use and its last prediction"),
+    ("logs", "The most recent log lines, /logs 50 for more"),
+    ("pause", "Stop opening new positions"),
+    ("resume", "Open new positions again"),
+    ("sellnow", "Sell the open position at market price"),
+    ("shutdown", "Stop the bot"),
+    ("help", "List the commands"),
+];
+
+/// Query answered by the telegram bot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TelegramCommand {
+    Help,
+    Status,
+    Position,
+    Pnl,
+    Model,
+    /// The amount of log lines to send.
+    Logs(usize),
+    Pause,
+    Resume,
+    SellNow,
+    Shutdown,
+}
+
+impl TelegramCommand {
+    /// Parses a message like `/logs 50`, returns `None` when it isn't a known command.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut words = text.split_whitespace();
+        let command = words.next()?.strip_prefix('/')?;
+        // In groups, commands are addressed to a bot as `/status@bot_name`.
+        let command = command.split('@').next().unwrap_or(command);
+
+        match command.to_lowercase().as_str() {
+            "start" | "help" => Some(TelegramCommand::Help),
+            "status" => Some(TelegramCommand::Status),
+            "position" => Some(TelegramCommand::Position),
+            "pnl" => Some(TelegramCommand::Pnl),
+            "model" => Some(TelegramCommand::Model),
+            "pause" => Some(TelegramCommand::Pause),
+            "resume" => Some(TelegramCommand::Resume),
+            "sellnow" => Some(TelegramCommand::SellNow),
+            "shutdown" => Some(TelegramCommand::Shutdown),
+            "logs" => Some(TelegramCommand::Logs(
+                words
+                    .next()
+                    .and_then(|count| count.parse().ok())
+                    .unwrap_or(DEFAULT_LOG_LINES),
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// The chats and users the bot answers to, everyone else is ignored.
+pub struct AllowList {
+    chats: HashSet<ChatId>,
+    users: HashSet<UserId>,
+    /// Log the messages that are ignored.
+    audit: bool,
+}
+
+impl AllowList {
+    pub fn from_config(config: &TelegramConfig) -> Self {
+        Self {
+            chats: std::iter::once(config.chat_id)
+                .chain(config.allowed_chats.iter().copied())
+                .map(ChatId)
+                .collect(),
+            users: config.allowed_users.iter().copied().map(UserId).collect(),
+            audit: config.audit_log,
+        }
+    }
+
+    /// Whether a message from `user` in `chat` may be answered.
+    /// The chat has to be allowed, and so does the user unless no users are listed.
+    pub fn allows(&self, chat: ChatId, user: Option<UserId>) -> bool {
+        self.chats.contains(&chat)
+            && (self.users.is_empty() || matches!(user, Some(user) if self.users.contains(&user)))
+    }
+
+    /// Whether the message may be answered, logging it when it's ignored and auditing is enabled.
+    fn check_message(&self, msg: &Message) -> bool {
+        let user = msg.from().map(|user| user.id);
+        if self.allows(msg.chat.id, user) {
+            return true;
+        }
+
+        if self.audit {
+            warn!(
+                "Ignored message from unauthorized chat {} (user {}): {:?}",
+                msg.chat.id,
+                user.map_or(String::from("unknown"), |user| user.to_string()),
+                msg.text().unwrap_or_default()
+            );
+        }
+        false
+    }
+
+    /// Whether the button press may be answered, logging it when it's ignored and auditing is enabled.
+    fn check_callback(&self, query: &CallbackQuery) -> bool {
+        let chat = query.message.as_ref().map(|msg| msg.chat.id);
+        let allowed = match chat {
+            Some(chat) => self.allows(chat, Some(query.from.id)),
+            None => self.users.contains(&query.from.id),
+        };
+        if allowed {
+            return true;
+        }
+
+        if self.audit {
+            warn!(
+                "Ignored button press from unauthorized user {} in chat {}: {:?}",
+                describe_user(&query.from),
+                chat.map_or(String::from("unknown"), |chat| chat.to_string()),
+                query.data.as_deref().unwrap_or_default()
+            );
+        }
+        false
+    }
+}
+
+/// What the command handler needs to answer and carry out the commands.
+pub struct CommandContext {
+    pub status: Arc<Status>,
+    pub logs: Arc<RecentLogs>,
+    pub controls: UnboundedSender<Control>,
+}
+
+impl CommandContext {
+    /// Carries out the command and returns the reply.
+    /// A shutdown is only replied to, it's up to the caller to trigger it once the reply was sent.
+    pub fn handle(&self, command: TelegramCommand) -> String {
+        let status = self.status.snapshot();
+        match command {
+            TelegramCommand::Help => help(),
+            TelegramCommand::Status => format!(
+                "Trading {} for {}{}{}.\n{}\n{}",
+                status.symbol,
+                format_duration(now().saturating_sub(status.started_at)),
+                if status.test { " in test mode" } else { "" },
+                if status.paused {
+                    ", opening new positions is paused"
+                } else {
+                    ""
+                },
+                describe_position(&status),
+                describe_profit(&status)
+            ),
+            TelegramCommand::Position => describe_position(&status),
+            TelegramCommand::Pnl => describe_profit(&status),
+            TelegramCommand::Model => describe_model(&status),
+            TelegramCommand::Logs(count) => {
+                let lines = self.logs.tail(count);
+                if lines.is_empty() {
+                    return String::from("Nothing was logged yet.");
+                }
+
+                // Drop the oldest lines that don't fit in a single message.
+                let mut length = 0;
+                let lines: Vec<_> = lines
+                    .iter()
+                    .rev()
+                    .take_while(|line| {
+                        length += line.chars().count() + 1;
+                        length <= MAX_MESSAGE_LENGTH
+                    })
+                    .collect();
+                lines
+                    .into_iter()
+                    .rev()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            TelegramCommand::Pause => self.control(
+                Control::Pause,
+                "Pausing, no new positions are opened until /resume. An open position is still sold as usual.",
+            ),
+            TelegramCommand::Resume => self.control(
+                Control::Resume,
+                "Resuming, a new position may be opened from the next candle.",
+            ),
+            TelegramCommand::SellNow => match status.position {
+                Position::Flat => String::from("There's no open position to sell."),
+                _ => self.control(
+                    Control::SellNow,
+                    "Selling the open position at market price with the next price update.",
+                ),
+            },
+            TelegramCommand::Shutdown => String::from("Shutting down."),
+        }
+    }
+
+    /// Passes the decision of an approve or reject button on to the strategy, and returns the answer to show.
+    pub fn decide(&self, data: &str, by: String) -> String {
+        let (request, approved) = match parse_decision(data) {
+            Some(decision) => decision,
+            None => return String::from("Sorry, I don't know that button."),
+        };
+
+        if !self.status.decide_approval(request) {
+            return String::from("Too late, this trade request expired or was already decided.");
+        }
+
+        info!(
+            "Trade {} {} by {}.",
+            request,
+            if approved { "approved" } else { "rejected" },
+            by
+        );
+        let reply = if approved {
+            format!("Approved by {}.", by)
+        } else {
+            format!("Rejected by {}.", by)
+        };
+        self.control(
+            Control::Decide(Decision {
+                request,
+                approved,
+                by,
+            }),
+            &reply,
+        )
+    }
+
+    fn control(&self, control: Control, reply: &str) -> String {
+        match self.controls.send(control) {
+            Ok(()) => reply.to_string(),
+            Err(_) => String::from("The trading bot isn't running anymore."),
+        }
+    }
+}
+
+fn help() -> String {
+    COMMANDS
+        .iter()
+        .map(|(command, description)| format!("/{} - {}", command, description))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn describe_position(status: &StatusSnapshot) -> String {
+    match &status.position {
+        Position::Flat => String::from("No open position."),
+        Position::Buying { amount, target } => format!(
+            "Buying {} for {}, to sell at {}.",
+            status.symbol, amount, target
+        ),
+        Position::Holding {
+            quantity,
+            price,
+            target,
+            opened_at,
+            take_profit_order,
+            ..
+        } => {
+            let order = match take_profit_order {
+                Some(id) => format!(" with take-profit order {}", id),
+                None => String::new(),
+            };
+            format!(
+                "Holding {} {} bought at {} on {}, selling at {} ({:+.2}%){}.",
+                quantity,
+                status.symbol,
+                price,
+                format_timestamp(Duration::from_millis(*opened_at)),
+                target,
+                (target / price - 1.0) * 100.0,
+                order
+            )
+        }
+        Position::Selling {
+            quantity, reason, ..
+        } => format!("Selling {} {} ({}).", quantity, status.symbol, reason),
+    }
+}
+
+fn describe_profit(status: &StatusSnapshot) -> String {
+    format!(
+        "Realized a profit of {} USD over {} closed positions ({} profitable).",
+        status.realized_profit, status.closed_positions, status.profitable_positions
+    )
+}
+
+fn describe_model(status: &StatusSnapshot) -> String {
+    let model = match &status.model {
+        Some(model) => format!(
+            "Model {} predicts {:?} from {} features, trained on the candles from {} to {} ({} samples, rmse: {}, mae: {}).",
+            model.version,
+            model.label,
+            model.features.len(),
+            format_timestamp(Duration::from_millis(model.window_start)),
+            format_timestamp(Duration::from_millis(model.window_end)),
+            model.metrics.samples,
+            model.metrics.rmse,
+            model.metrics.mae
+        ),
+        None => String::from("No model was loaded or trained yet."),
+    };
+    let prediction = match &status.last_prediction {
+        Some(prediction) => format!(
+            "Last prediction on {}: {:?} of {} (open: {}, current: {}).",
+            format_timestamp(prediction.at),
+            prediction.label,
+            prediction.predicted,
+            prediction.open,
+            prediction.close
+        ),
+        None => String::from("No prediction was made yet."),
+    };
+    format!("{}\n{}", model, prediction)
+}
+
+/// Buttons to approve or reject the trade of an approval request.
+fn approval_keyboard(request: u64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback("Approve", format!("approve:{}", request)),
+        InlineKeyboardButton::callback("Reject", format!("reject:{}", request)),
+    ]])
+}
+
+/// Parses the data of an approval button into the request ID and whether it was approved.
+fn parse_decision(data: &str) -> Option<(u64, bool)> {
+    let (decision, request) = data.split_once(':')?;
+    let approved = match decision {
+        "approve" => true,
+        "reject" => false,
+        _ => return None,
+    };
+    Some((request.parse().ok()?, approved))
+}
+
+/// Name and ID of a user, for the logs.
+fn describe_user(user: &User) -> String {
+    match &user.username {
+        Some(username) => format!("@{} ({})", username, user.id),
+        None => format!("{} ({})", user.full_name(), user.id),
+    }
+}
+
+async fn answer_message(
+    bot: Bot,
+    msg: Message,
+    context: Arc<CommandContext>,
+    shutdown: Arc<Shutdown>,
+) -> ResponseResult<()> {
+    let command = msg.text().and_then(TelegramCommand::parse);
+    let reply = match command {
+        Some(command) => context.handle(command),
+        None => format!("Sorry, I don't know that command.\n{}", help()),
+    };
+    bot.send_message(msg.chat.id, reply).await?;
+
+    // Only now that the reply was sent, as the telegram bot stops along with the trading bot.
+    if command == Some(TelegramCommand::Shutdown) {
+        info!("Shutdown requested over telegram.");
+        shutdown.trigger();
+    }
+    respond(())
+}
+
+async fn answer_button(
+    bot: Bot,
+    query: CallbackQuery,
+    context: Arc<CommandContext>,
+) -> ResponseResult<()> {
+    let answer = context.decide(
+        query.data.as_deref().unwrap_or_default(),
+        describe_user(&query.from),
+    );
+    bot.answer_callback_query(query.id)
+        .text(answer.clone())
+        .await?;
+
+    // Replace the buttons with the decision, so it can't be made twice.
+    if let Some(msg) = query.message {
+        let text = format!("{}\n\n{}", msg.text().unwrap_or_default(), answer);
+        bot.edit_message_text(msg.chat.id, msg.id, text).await?;
+    }
+    respond(())
+}
+
+/// Sends the events to the configured chat, buy requests come with buttons to approve or reject them.
+pub struct TelegramNotifier {
+    bot: Bot,
+    chat_id: ChatId,
+}
+
+impl TelegramNotifier {
+    pub fn new(config: &TelegramConfig) -> Self {
+        Self {
+            bot: Bot::new(&config.bot_token),
+            chat_id: ChatId(config.chat_id),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for TelegramNotifier {
+    fn name(&self) -> &str {
+        "telegram"
+    }
+
+    async fn notify(&self, event: &TradeEvent) -> anyhow::Result<()> {
+        let request = self.bot.send_message(self.chat_id, event.to_string());
+        match event {
+            TradeEvent::ApprovalRequested { request: id, .. } => {
+                request.reply_markup(approval_keyboard(*id)).await?
+            }
+            _ => request.await?,
+        };
+        Ok(())
+    }
+}
+
+/// Answers the commands until a shutdown is requested, the events are sent by a `TelegramNotifier`.
+pub async fn start_telegram_bot(
+    config: TelegramConfig,
+    shutdown: Arc<Shutdown>,
+    status: Arc<Status>,
+    logs: Arc<RecentLogs>,
+    controls: UnboundedSender<Control>,
+) {
+    info!("Starting telegram bot.");
+
+    let telegram_bot = Bot::new(&config.bot_token);
+
+    let commands = COMMANDS
+        .iter()
+        .map(|(command, description)| BotCommand::new(*command, *description));
+    if let Err(err) = telegram_bot.set_my_commands(commands).await {
+        warn!("Failed to register the telegram commands: {}", err);
+    }
+
+    let allow_list = Arc::new(AllowList::from_config(&config));
+    let context = Arc::new(CommandContext {
+        status,
+        logs,
+        controls,
+    });
+    // Unauthorized senders don't get any reply, so they can't even tell the bot is running.
+    let messages = Update::filter_message()
+        .filter(|msg: Message, allow_list: Arc<AllowList>| allow_list.check_message(&msg))
+        .endpoint(answer_message);
+    let buttons = Update::filter_callback_query()
+        .filter(|query: CallbackQuery, allow_list: Arc<AllowList>| {
+            allow_list.check_callback(&query)
+        })
+        .endpoint(answer_button);
+    let handler = dptree::entry().branch(messages).branch(buttons);
+    let mut dispatcher = Dispatcher::builder(telegram_bot, handler)
+        .dependencies(dptree::deps![allow_list, context, shutdown.clone()])
+        .build();
+
+    tokio::select! {
+        _ = dispatcher.dispatch() => (),
+        _ = shutdown.wait() => info!("Stopping telegram bot."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use teloxide::types::{ChatId, UserId};
+
+    use crate::{
+        config::TelegramConfig, control, events::TradeEvent, logging::RecentLogs,
+        position::Position, status::Status,
+    };
+
+    use super::{parse_decision, AllowList, CommandContext, TelegramCommand};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            TelegramCommand::parse("/status"),
+            Some(TelegramCommand::Status)
+        );
+        assert_eq!(
+            TelegramCommand::parse("/pnl@trading_bot"),
+            Some(TelegramCommand::Pnl)
+        );
+        assert_eq!(
+            TelegramCommand::parse("/logs 50"),
+            Some(TelegramCommand::Logs(50))
+        );
+        assert_eq!(
+            TelegramCommand::parse("/logs"),
+            Some(TelegramCommand::Logs(20))
+        );
+        assert_eq!(TelegramCommand::parse("status"), None);
+        assert_eq!(
+            TelegramCommand::parse("/sellnow"),
+            Some(TelegramCommand::SellNow)
+        );
+        assert_eq!(TelegramCommand::parse("/buy"), None);
+
+        assert_eq!(
+            parse_decision("approve:1674940162060"),
+            Some((1674940162060, true))
+        );
+        assert_eq!(parse_decision("reject:1"), Some((1, false)));
+        assert_eq!(parse_decision("approve"), None);
+        assert_eq!(parse_decision("buy:1"), None);
+    }
+
+    #[test]
+    fn test_handle() {
+        let (controls, rx) = control::channel();
+        let context = CommandContext {
+            status: Arc::new(Status::new("BTCUSDT", true)),
+            logs: Arc::new(RecentLogs::new(10)),
+            controls,
+        };
+        context.status.set_position(Position::Holding {
+            quantity: 0.002,
+            price: 20000.0,
+            target: 20500.0,
+            opened_at: 1674940162060,
+            highest_price: 20000.0,
+            take_profit_order: None,
+        });
+        context.logs.push(String::from("first"));
+        context.logs.push(String::from("second"));
+
+        let handle = |command| context.handle(command);
+        assert_eq!(
+            handle(TelegramCommand::Position),
+            "Holding 0.002 BTCUSDT bought at 20000 on 2023-01-28T21:09:22Z, selling at 20500 (+2.50%)."
+        );
+        assert_eq!(
+            handle(TelegramCommand::Status),
+            "Trading BTCUSDT for 0m in test mode.\nHolding 0.002 BTCUSDT bought at 20000 on 2023-01-28T21:09:22Z, selling at 20500 (+2.50%).\nRealized a profit of 0 USD over 0 closed positions (0 profitable)."
+        );
+        assert_eq!(handle(TelegramCommand::Logs(1)), "second");
+        assert_eq!(handle(TelegramCommand::Logs(5)), "first\nsecond");
+
+        // Control commands reach the strategy.
+        handle(TelegramCommand::Pause);
+        handle(TelegramCommand::SellNow);
+        context.status.record(&TradeEvent::ApprovalRequested {
+            request: 1,
+            symbol: String::from("BTCUSDT"),
+            amount: 40.0,
+            predicted: 20500.0,
+            open: 20000.0,
+            close: 20100.0,
+            expected_profit: 0.8,
+            expected_profit_percentage: 1.99,
+            timeout: 300,
+        });
+        assert_eq!(
+            context.decide("approve:1", String::from("@alice (7)")),
+            "Approved by @alice (7)."
+        );
+        // Only the first decision counts.
+        assert_eq!(
+            context.decide("reject:1", String::from("@bob (8)")),
+            "Too late, this trade request expired or was already decided."
+        );
+        rx.receive();
+        assert!(rx.is_paused());
+        assert!(rx.take_sell_request());
+        assert_eq!(
+            rx.take_decision(1).map(|decision| decision.by),
+            Some(String::from("@alice (7)"))
+        );
+    }
+
+    #[test]
+    fn test_allow_list() {
+        let mut config = TelegramConfig {
+            bot_token: String::from("123456789:blablabla"),
+            chat_id: 42,
+            allowed_chats: vec![-100],
+            allowed_users: Vec::new(),
+            audit_log: false,
+        };
+        let allow_list = AllowList::from_config(&config);
+        assert!(allow_list.allows(ChatId(42), None));
+        assert!(allow_list.allows(ChatId(-100), Some(UserId(1))));
+        assert!(!allow_list.allows(ChatId(1), Some(UserId(1))));
+
+        // Listed users restrict who may use the bot inside the allowed chats.
+        config.allowed_users = vec![7];
+        let allow_list = AllowList::from_config(&config);
+        assert!(allow_list.allows(ChatId(-100), Some(UserId(7))));
+        assert!(!allow_list.allows(ChatId(-100), Some(UserId(1))));
+        assert!(!allow_list.allows(ChatId(42), None));
+        assert!(!allow_list.allows(ChatId(1), Some(UserId(7))));
+    }
+}
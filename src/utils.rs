@@ -1,74 +1,5 @@
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::{
-    binance_market::{
-        BinanceKlineInterval, BinanceKlineOptions, BinanceMarket, BINANCE_MARKET_EPOCH,
-        BINANCE_MAX_KLINES,
-    },
-    config::{try_load_config, DEFAULT_CONFIG},
-};
-
-/// Fetch ALL klines from Binance and write them to a CSV file.
-pub fn save_binance_dataset(file_path: &str, symbol: &str) {
-    let config = try_load_config(DEFAULT_CONFIG);
-
-    let binance_market = BinanceMarket::new(config.binance);
-    let binance_kline_options = BinanceKlineOptions {
-        pair: symbol.into(),
-        interval: BinanceKlineInterval::Hourly,
-        limit: None,
-        start: Some(BINANCE_MARKET_EPOCH),
-        end: None,
-    };
-
-    let mut writer = csv::WriterBuilder::new()
-        .delimiter(b',')
-        .from_path(file_path)
-        .unwrap();
-
-    writer
-        .write_record(&[
-            "open_time",
-            "close_time",
-            "symbol",
-            "open",
-            "high",
-            "low",
-            "close",
-            "volume",
-            "quote_asset_volume",
-        ])
-        .expect("failed to write header");
-
-    let mut i: u64 = 0;
-
-    for kline in binance_market.get_klines(binance_kline_options) {
-        let count = i % BINANCE_MAX_KLINES as u64;
-
-        if count == 0 && i != 0 {
-            println!();
-        }
-
-        print!("\rwriting record {} / {}", count + 1, BINANCE_MAX_KLINES);
-
-        writer
-            .write_record(&[
-                kline.open_time.to_string(),
-                kline.close_time.to_string(),
-                symbol.to_string(),
-                kline.open.to_string(),
-                kline.high.to_string(),
-                kline.low.to_string(),
-                kline.close.to_string(),
-                kline.volume.to_string(),
-                kline.quote_asset_volume.to_string(),
-            ])
-            .expect("failed to write record");
-
-        i += 1;
-    }
-}
-
 pub fn calculate_profit(investment: f64, initial_price: f64, selling_price: f64) -> (f64, f64) {
     let price = investment * (selling_price / initial_price) - investment;
     let percentage = (price / investment) * 100.0;
@@ -129,6 +60,68 @@ pub fn ceil_hour(timestamp: Duration) -> Duration {
     Duration::from_secs(upper_hour)
 }
 
+/// Returns the number of days since the unix epoch of the given date in the proleptic gregorian calendar.
+/// See http://howardhinnant.github.io/date_algorithms.html#days_from_civil.
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Returns the date (year, month, day) of the given number of days since the unix epoch.
+/// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_part = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_part + 2) / 5 + 1) as u32;
+    let month = if month_part < 10 {
+        month_part + 3
+    } else {
+        month_part - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Formats a unix epoch timestamp as an ISO 8601 date and time in UTC, e.g. `2023-01-28T21:09:22Z`.
+pub fn format_timestamp(timestamp: Duration) -> String {
+    let secs = timestamp.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs_of_day = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Formats a duration in whole minutes for humans, e.g. `2d 3h 15m`.
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    let (days, hours, minutes) = (minutes / 1440, minutes % 1440 / 60, minutes % 60);
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
 /// Returns the current time as a unix epoch timestamp encapsulated in a `Duration`.
 /// Use `as_millis()` to acess the value accordingly.
 pub fn now() -> Duration {
@@ -140,7 +133,10 @@ pub fn now() -> Duration {
 mod tests {
     use std::time::Duration;
 
-    use crate::utils::{calculate_profit, ceil_hour, floor_hour};
+    use crate::utils::{
+        calculate_profit, ceil_hour, civil_from_days, days_from_civil, floor_hour, format_duration,
+        format_timestamp,
+    };
 
     use super::earlier;
 
@@ -194,4 +190,31 @@ mod tests {
             expected_timestamp
         );
     }
+
+    #[test]
+    fn test_civil_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2023, 1, 28), 19385);
+        assert_eq!(days_from_civil(2024, 3, 1), 19783);
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19385), (2023, 1, 28));
+        assert_eq!(civil_from_days(19782), (2024, 2, 29));
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            format_timestamp(Duration::from_millis(1674940162060)),
+            "2023-01-28T21:09:22Z"
+        );
+        assert_eq!(format_duration(Duration::from_secs(59)), "0m");
+        assert_eq!(
+            format_duration(Duration::from_secs(3 * 3600 + 15 * 60)),
+            "3h 15m"
+        );
+        assert_eq!(
+            format_duration(Duration::from_secs(2 * 86400 + 3 * 3600 + 15 * 60 + 30)),
+            "2d 3h 15m"
+        );
+    }
 }